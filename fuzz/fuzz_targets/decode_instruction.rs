@@ -0,0 +1,14 @@
+#![no_main]
+
+use chip8_emulator::instruction::Instruction;
+use libfuzzer_sys::fuzz_target;
+
+// Decoding and inspecting any word must not panic, undecodable words return an error
+fuzz_target!(|raw_instruction: u16| {
+    if let Ok(instruction) = Instruction::try_from_u16(raw_instruction) {
+        let _ = instruction.to_string();
+        let _ = instruction.category();
+        let _ = instruction.registers_written();
+        let _ = instruction.to_u16();
+    }
+});
@@ -0,0 +1,100 @@
+use anyhow::{anyhow, Context, Result};
+
+use crate::cpu::CompatibilityMode;
+
+const MIN_SPEED: u64 = 60;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub rom: Option<String>,
+    pub config: Option<String>,
+    pub speed: Option<u64>,
+    pub mode: Option<CompatibilityMode>,
+}
+
+impl Args {
+    pub fn from_env() -> Result<Self> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut result = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rom" => result.rom = Some(next_value(&mut args, &arg)?),
+                "--config" => result.config = Some(next_value(&mut args, &arg)?),
+                "--speed" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let speed = value
+                        .parse::<u64>()
+                        .with_context(|| format!("Invalid speed '{}'", value))?;
+                    if speed < MIN_SPEED {
+                        return Err(anyhow!(
+                            "Speed must be at least {} Hz, got {}",
+                            MIN_SPEED,
+                            speed
+                        ));
+                    }
+                    result.speed = Some(speed);
+                }
+                "--mode" => result.mode = Some(next_value(&mut args, &arg)?.parse()?),
+                _ => return Err(anyhow!("Unknown argument '{}'", arg)),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, name: &str) -> Result<String> {
+    args.next()
+        .ok_or_else(|| anyhow!("Missing value for argument '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args> {
+        Args::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn parses_all_arguments() {
+        let args = parse(&[
+            "--rom",
+            "./roms/ibm-logo.ch8",
+            "--config",
+            "chip8.toml",
+            "--speed",
+            "1000",
+            "--mode",
+            "schip",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            Args {
+                rom: Some("./roms/ibm-logo.ch8".to_string()),
+                config: Some("chip8.toml".to_string()),
+                speed: Some(1000),
+                mode: Some(CompatibilityMode::SuperChip),
+            },
+            args
+        );
+    }
+
+    #[test]
+    fn no_arguments_result_in_defaults() {
+        assert_eq!(Args::default(), parse(&[]).unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        assert!(parse(&["--rom"]).is_err());
+        assert!(parse(&["--speed", "fast"]).is_err());
+        assert!(parse(&["--speed", "10"]).is_err());
+        assert!(parse(&["--mode", "xochip"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+}
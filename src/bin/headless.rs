@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Context, Result};
+
+use chip8_emulator::{cpu::Cpu, keypad::MockKeypad, rom::Rom};
+
+#[derive(Debug, PartialEq)]
+struct HeadlessArgs {
+    rom: String,
+    cycles: u64,
+}
+
+impl HeadlessArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut rom = None;
+        let mut cycles = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rom" => rom = Some(next_value(&mut args, &arg)?),
+                "--cycles" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let value = value
+                        .parse::<u64>()
+                        .with_context(|| format!("Invalid cycle count '{}'", value))?;
+                    cycles = Some(value);
+                }
+                _ => return Err(anyhow!("Unknown argument '{}'", arg)),
+            }
+        }
+
+        Ok(Self {
+            rom: rom.ok_or_else(|| anyhow!("Missing required argument '--rom'"))?,
+            cycles: cycles.ok_or_else(|| anyhow!("Missing required argument '--cycles'"))?,
+        })
+    }
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, name: &str) -> Result<String> {
+    args.next()
+        .ok_or_else(|| anyhow!("Missing value for argument '{}'", name))
+}
+
+/// Runs a rom for a fixed number of cycles without a window and prints the final cpu state
+fn main() -> Result<()> {
+    let args = HeadlessArgs::parse(std::env::args().skip(1))
+        .with_context(|| "Usage: headless --rom <path> --cycles <n>")?;
+
+    let rom = Rom::from_file(&args.rom)?;
+    let mut cpu = Cpu::<MockKeypad>::from_rom(rom)?;
+    let (completed, error) = cpu.run_for_cycles(args.cycles);
+    if let Some(err) = error {
+        return Err(err.context(format!("Cpu failed in cycle {}", completed)));
+    }
+    if cpu.has_exited() {
+        println!("Rom exited in cycle {}", completed);
+    }
+
+    println!("PC: 0x{:0>4X}", cpu.pc());
+    println!("I: 0x{:0>4X}", cpu.index_value());
+    println!("DT: 0x{:0>2X}", cpu.delay_timer.get());
+    println!("ST: 0x{:0>2X}", cpu.sound_timer.get());
+    println!("{:#?}", cpu.registers());
+    println!("{}", cpu.display().as_text_art('█', ' '));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<HeadlessArgs> {
+        HeadlessArgs::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn parses_rom_and_cycles() {
+        let args = parse(&["--rom", "./roms/ibm-logo.ch8", "--cycles", "100"]).unwrap();
+
+        assert_eq!(
+            HeadlessArgs {
+                rom: "./roms/ibm-logo.ch8".to_string(),
+                cycles: 100,
+            },
+            args
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_arguments() {
+        assert!(parse(&["--rom", "./roms/ibm-logo.ch8"]).is_err());
+        assert!(parse(&["--cycles", "100"]).is_err());
+        assert!(parse(&["--rom", "./roms/ibm-logo.ch8", "--cycles", "many"]).is_err());
+        assert!(parse(&["--speed", "100"]).is_err());
+    }
+}
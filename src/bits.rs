@@ -1,12 +1,13 @@
+use anyhow::{anyhow, Result};
 use std::ops::Deref;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct U4(u8);
 
 impl U4 {
-    pub fn new(value: u8) -> Self {
+    pub const fn new(value: u8) -> Self {
         if value > 0xF {
-            panic!("Tried instancing u4 with value {}", value);
+            panic!("U4 value out of range");
         }
 
         U4(value)
@@ -27,14 +28,14 @@ impl From<U4> for usize {
     }
 }
 
-pub fn split_u16(value: u16) -> (u8, u8) {
+pub const fn split_u16(value: u16) -> (u8, u8) {
     let upper = (value & 0b11111111_00000000) >> 8;
     let lower = value & 0b11111111;
 
     (upper as u8, lower as u8)
 }
 
-pub fn split_u8(value: u8) -> (U4, U4) {
+pub const fn split_u8(value: u8) -> (U4, U4) {
     let upper = (value & 0b1111_0000) >> 4;
     let lower = value & 0b1111;
 
@@ -59,6 +60,21 @@ pub fn join_to_u8(n1: U4, n2: U4) -> u8 {
     (*n1 << 4) + *n2
 }
 
+/// Joins consecutive pairs of nibbles into bytes, the first nibble of a pair being the upper one
+pub fn join_u4_vec(nibbles: &[U4]) -> Result<Vec<u8>> {
+    if !nibbles.len().is_multiple_of(2) {
+        return Err(anyhow!(
+            "Expected an even number of nibbles, got {}",
+            nibbles.len()
+        ));
+    }
+
+    Ok(nibbles
+        .chunks(2)
+        .map(|pair| join_to_u8(pair[0], pair[1]))
+        .collect())
+}
+
 pub fn join_nibbles(n1: u8, n2: u8, n3: u8, n4: u8) -> u16 {
     let n1 = n1 as u16;
     let n2 = n2 as u16;
@@ -67,7 +83,34 @@ pub fn join_nibbles(n1: u8, n2: u8, n3: u8, n4: u8) -> u16 {
     (n1 << 12) + (n2 << 8) + (n3 << 4) + n4
 }
 
-pub fn split_instruction(instruction: u16) -> (U4, U4, U4, U4) {
+/// Returns the nibble at the position, 0 being the lowest and 3 the highest nibble
+pub fn nibble_at(value: u16, position: u8) -> U4 {
+    if position > 3 {
+        panic!("Nibble position {} is out of range 0-3", position);
+    }
+
+    U4::new(((value >> (position * 4)) & 0xF) as u8)
+}
+
+/// Replaces the nibble at the position, 0 being the lowest and 3 the highest nibble
+pub fn set_nibble_at(value: u16, position: u8, nibble: U4) -> u16 {
+    if position > 3 {
+        panic!("Nibble position {} is out of range 0-3", position);
+    }
+
+    let shift = position * 4;
+    (value & !(0xF << shift)) | ((*nibble as u16) << shift)
+}
+
+pub fn rotate_left_u8(value: u8, n: u8) -> u8 {
+    value.rotate_left(n as u32)
+}
+
+pub fn rotate_right_u8(value: u8, n: u8) -> u8 {
+    value.rotate_right(n as u32)
+}
+
+pub const fn split_instruction(instruction: u16) -> (U4, U4, U4, U4) {
     let (upper_byte, lower_byte) = split_u16(instruction);
     let upper_nibbles = split_u8(upper_byte);
     let lower_nibbles = split_u8(lower_byte);
@@ -79,3 +122,61 @@ pub fn split_instruction(instruction: u16) -> (U4, U4, U4, U4) {
         lower_nibbles.1,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLEAR_SCREEN_NIBBLES: (U4, U4, U4, U4) = split_instruction(0x00E0);
+
+    #[test]
+    fn split_instruction_is_usable_in_const_context() {
+        let (n1, n2, n3, n4) = CLEAR_SCREEN_NIBBLES;
+
+        assert_eq!((0x0, 0x0, 0xE, 0x0), (*n1, *n2, *n3, *n4));
+    }
+
+    #[test]
+    #[should_panic(expected = "U4 value out of range")]
+    fn u4_rejects_values_above_15() {
+        U4::new(0x10);
+    }
+
+    #[test]
+    fn nibbles_are_read_and_replaced_by_position() {
+        assert_eq!(0xA, *nibble_at(0xABCD, 3));
+        assert_eq!(0xD, *nibble_at(0xABCD, 0));
+        assert_eq!(0xA000, set_nibble_at(0x0000, 3, U4::new(0xA)));
+        assert_eq!(0xAB5D, set_nibble_at(0xABCD, 1, U4::new(0x5)));
+    }
+
+    #[test]
+    fn nibbles_are_joined_into_bytes() {
+        assert_eq!(
+            vec![0xAB],
+            join_u4_vec(&[U4::new(0xA), U4::new(0xB)]).unwrap()
+        );
+        assert_eq!(
+            vec![0x00, 0xE0],
+            join_u4_vec(&[U4::new(0x0), U4::new(0x0), U4::new(0xE), U4::new(0x0)]).unwrap()
+        );
+        assert!(join_u4_vec(&[]).unwrap().is_empty());
+
+        let err = join_u4_vec(&[U4::new(0xA), U4::new(0xB), U4::new(0xC)]).unwrap_err();
+        assert_eq!("Expected an even number of nibbles, got 3", err.to_string());
+    }
+
+    #[test]
+    fn bytes_are_rotated() {
+        assert_eq!(0b0000_0011, rotate_left_u8(0b1000_0001, 1));
+        assert_eq!(0b1100_0000, rotate_right_u8(0b1000_0001, 1));
+        assert_eq!(0b1010_0110, rotate_left_u8(0b1010_0110, 8));
+        assert_eq!(0b1010_0110, rotate_right_u8(0b1010_0110, 8));
+    }
+
+    #[test]
+    #[should_panic(expected = "Nibble position 4 is out of range 0-3")]
+    fn nibble_at_rejects_invalid_position() {
+        nibble_at(0xABCD, 4);
+    }
+}
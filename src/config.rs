@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+
+use crate::cpu::{CompatibilityMode, CpuConfig, INSTRUCTION_RATE};
+
+pub const CONFIG_PATH: &str = "./chip8.toml";
+
+/// Keys for the chip8 values 0x0 to 0xF
+pub const DEFAULT_KEY_MAPPING: &str = "X123QWEASDZC4RFV";
+
+pub const MAX_RECENT_ROMS: usize = 10;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmulatorConfig {
+    pub rom_directory: String,
+    pub cpu_speed: u64,
+    pub foreground_color: [u8; 3],
+    pub background_color: [u8; 3],
+    /// Color of pixels only set in the second XO-CHIP plane
+    pub plane2_color: [u8; 3],
+    /// Color of pixels set in both XO-CHIP planes
+    pub both_planes_color: [u8; 3],
+    pub key_mapping: String,
+    pub compatibility_mode: CompatibilityMode,
+    /// Skip undecodable instructions instead of stopping the rom
+    pub lenient: bool,
+    /// Paths of the last loaded roms, the most recent first
+    pub recent_roms: VecDeque<String>,
+    /// Window size at startup, the panels scale with the window
+    pub window_width: i32,
+    pub window_height: i32,
+    pub fullscreen: bool,
+}
+
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        Self {
+            rom_directory: "./roms".to_string(),
+            cpu_speed: INSTRUCTION_RATE,
+            foreground_color: [255, 255, 255],
+            background_color: [0, 0, 0],
+            plane2_color: [255, 102, 0],
+            both_planes_color: [102, 34, 0],
+            key_mapping: DEFAULT_KEY_MAPPING.to_string(),
+            compatibility_mode: CompatibilityMode::default(),
+            lenient: false,
+            recent_roms: VecDeque::new(),
+            window_width: 1280,
+            window_height: 768,
+            fullscreen: false,
+        }
+    }
+}
+
+impl EmulatorConfig {
+    /// Loads the config from the given path. A missing file results in the default config.
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+
+        let content =
+            std::fs::read_to_string(path).with_context(|| format!("Failed reading '{}'", path))?;
+        Self::from_toml(&content).with_context(|| format!("Failed parsing '{}'", path))
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(path, content).with_context(|| format!("Failed writing '{}'", path))
+    }
+
+    /// Moves the rom to the front of the recent roms, dropping the oldest beyond `MAX_RECENT_ROMS`.
+    /// Returns false if the rom already was the most recent one.
+    pub fn add_recent_rom(&mut self, rom_path: &str) -> bool {
+        if self
+            .recent_roms
+            .front()
+            .is_some_and(|path| path == rom_path)
+        {
+            return false;
+        }
+
+        self.recent_roms.retain(|path| path != rom_path);
+        self.recent_roms.push_front(rom_path.to_string());
+        self.recent_roms.truncate(MAX_RECENT_ROMS);
+        true
+    }
+
+    /// Colors of the four pixel values combined from both display planes
+    pub fn display_colors(&self) -> [[u8; 3]; 4] {
+        [
+            self.background_color,
+            self.foreground_color,
+            self.plane2_color,
+            self.both_planes_color,
+        ]
+    }
+
+    /// Creates the cpu config for the configured speed and mode
+    pub fn cpu_config(&self) -> CpuConfig {
+        CpuConfig {
+            cpu_rate_hz: self.cpu_speed,
+            lenient: self.lenient,
+            ..CpuConfig::for_mode(self.compatibility_mode)
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_is_deserialized_from_toml() {
+        let content = r#"
+            rom_directory = "./my-roms"
+            cpu_speed = 1000
+            foreground_color = [0, 255, 0]
+            background_color = [10, 20, 30]
+            plane2_color = [1, 2, 3]
+            both_planes_color = [4, 5, 6]
+            key_mapping = "0123456789ABCDEF"
+            compatibility_mode = "schip"
+            lenient = true
+            recent_roms = ["./roms/pong.ch8"]
+            window_width = 1366
+            window_height = 720
+            fullscreen = true
+        "#;
+
+        let config = EmulatorConfig::from_toml(content).unwrap();
+
+        assert_eq!(
+            EmulatorConfig {
+                rom_directory: "./my-roms".to_string(),
+                cpu_speed: 1000,
+                foreground_color: [0, 255, 0],
+                background_color: [10, 20, 30],
+                plane2_color: [1, 2, 3],
+                both_planes_color: [4, 5, 6],
+                key_mapping: "0123456789ABCDEF".to_string(),
+                compatibility_mode: CompatibilityMode::SuperChip,
+                lenient: true,
+                recent_roms: VecDeque::from(["./roms/pong.ch8".to_string()]),
+                window_width: 1366,
+                window_height: 720,
+                fullscreen: true,
+            },
+            config
+        );
+    }
+
+    #[test]
+    fn missing_fields_use_default_values() {
+        let config = EmulatorConfig::from_toml("cpu_speed = 1000").unwrap();
+
+        assert_eq!(
+            EmulatorConfig {
+                cpu_speed: 1000,
+                ..Default::default()
+            },
+            config
+        );
+    }
+
+    #[test]
+    fn config_survives_serialization() {
+        let config = EmulatorConfig {
+            compatibility_mode: CompatibilityMode::Chip48,
+            ..Default::default()
+        };
+
+        let content = toml::to_string_pretty(&config).unwrap();
+
+        assert_eq!(config, EmulatorConfig::from_toml(&content).unwrap());
+    }
+
+    #[test]
+    fn recent_roms_are_deduplicated_and_limited() {
+        let mut config = EmulatorConfig::default();
+
+        for idx in 0..12 {
+            config.add_recent_rom(&format!("rom{}.ch8", idx));
+        }
+        assert!(config.add_recent_rom("rom5.ch8"));
+        assert!(!config.add_recent_rom("rom5.ch8"));
+
+        assert_eq!(MAX_RECENT_ROMS, config.recent_roms.len());
+        assert_eq!(
+            Some("rom5.ch8"),
+            config.recent_roms.front().map(String::as_str)
+        );
+        assert_eq!(
+            1,
+            config
+                .recent_roms
+                .iter()
+                .filter(|path| *path == "rom5.ch8")
+                .count()
+        );
+        assert!(!config.recent_roms.contains(&"rom1.ch8".to_string()));
+    }
+}
@@ -1,7 +1,11 @@
 use anyhow::{anyhow, Context, Result};
-use rand::Rng;
+use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 
+use crate::bits::join_bytes;
+use crate::instruction::LOAD_LONG_OPCODE;
 use crate::keypad::Keypad;
 use crate::rom::Rom;
 use crate::timer::Timer;
@@ -14,7 +18,111 @@ use crate::{
 };
 
 pub const INSTRUCTION_RATE: u64 = 700;
+pub const RPL_FLAG_COUNT: usize = 16;
+pub const MAX_STACK_DEPTH: usize = 16;
+/// Number of pixels the SUPER-CHIP horizontal scroll instructions move the display
+const SCROLL_DISTANCE: usize = 4;
+
+/// Selects which interpreter the quirks of the cpu are modelled after
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompatibilityMode {
+    #[default]
+    Chip8,
+    Chip48,
+    #[serde(rename = "schip")]
+    SuperChip,
+}
+
+impl CompatibilityMode {
+    fn resets_flag_on_logic(&self) -> bool {
+        *self == CompatibilityMode::Chip8
+    }
+
+    fn shifts_in_place(&self) -> bool {
+        *self != CompatibilityMode::Chip8
+    }
+
+    fn jumps_with_register_offset(&self) -> bool {
+        *self != CompatibilityMode::Chip8
+    }
+
+    /// Dxy0 draws a 16x16 sprite in the low resolution mode as well, instead of nothing
+    fn draws_extended_sprites_in_low_resolution(&self) -> bool {
+        *self == CompatibilityMode::SuperChip
+    }
+
+    /// Fx1E sets VF when I leaves the 12 bit address space, which SCTEST checks for
+    fn sets_flag_on_index_overflow(&self) -> bool {
+        *self == CompatibilityMode::SuperChip
+    }
+
+    fn index_increment(&self, register: U4) -> u16 {
+        match self {
+            CompatibilityMode::Chip8 => *register as u16 + 1,
+            CompatibilityMode::Chip48 => *register as u16,
+            CompatibilityMode::SuperChip => 0,
+        }
+    }
+}
+
+impl FromStr for CompatibilityMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "chip8" => Ok(CompatibilityMode::Chip8),
+            "chip48" => Ok(CompatibilityMode::Chip48),
+            "schip" => Ok(CompatibilityMode::SuperChip),
+            _ => Err(anyhow!(
+                "Unknown compatibility mode '{}', expected chip8, chip48 or schip",
+                value
+            )),
+        }
+    }
+}
+
+/// Settings the cpu is constructed with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuConfig {
+    pub compatibility_mode: CompatibilityMode,
+    pub cpu_rate_hz: u64,
+    /// Maximum number of nested subroutine calls
+    pub max_stack_depth: usize,
+    pub wrap_sprites: bool,
+    /// The logic instructions 8xy1, 8xy2 and 8xy3 reset VF
+    pub reset_vf_on_logic: bool,
+    /// Undecodable instructions are skipped instead of failing
+    pub lenient: bool,
+}
+
+impl Default for CpuConfig {
+    fn default() -> Self {
+        Self::for_mode(CompatibilityMode::default())
+    }
+}
+
+impl CpuConfig {
+    /// Creates the default config with the quirks of the given mode
+    pub fn for_mode(compatibility_mode: CompatibilityMode) -> Self {
+        Self {
+            compatibility_mode,
+            cpu_rate_hz: INSTRUCTION_RATE,
+            max_stack_depth: MAX_STACK_DEPTH,
+            wrap_sprites: false,
+            reset_vf_on_logic: compatibility_mode.resets_flag_on_logic(),
+            lenient: false,
+        }
+    }
 
+    /// Changes the mode together with the quirks that depend on it
+    pub fn set_compatibility_mode(&mut self, compatibility_mode: CompatibilityMode) {
+        self.compatibility_mode = compatibility_mode;
+        self.reset_vf_on_logic = compatibility_mode.resets_flag_on_logic();
+    }
+}
+
+#[derive(Clone)]
 pub struct VariableRegisters {
     registers: [u8; 16],
 }
@@ -24,7 +132,7 @@ impl VariableRegisters {
         VariableRegisters { registers: [0; 16] }
     }
 
-    fn set_value(&mut self, register: U4, value: u8) {
+    pub fn set_value(&mut self, register: U4, value: u8) {
         let idx = *register as usize;
         self.registers[idx] = value;
     }
@@ -38,13 +146,30 @@ impl VariableRegisters {
         let idx = *register as usize;
         self.registers[idx]
     }
+
+    /// Sets VF, the carry, borrow and collision flag of the arithmetic and draw instructions
+    pub fn set_flag(&mut self, value: bool) {
+        self.set_value(U4::new(0xF), value as u8);
+    }
+
+    pub fn get_flag(&self) -> bool {
+        self.get_value(U4::new(0xF)) != 0
+    }
+
+    /// Iterates over the registers V0 to VF and their values
+    pub fn iter(&self) -> impl Iterator<Item = (U4, u8)> + '_ {
+        self.registers
+            .iter()
+            .enumerate()
+            .map(|(idx, &value)| (U4::new(idx as u8), value))
+    }
 }
 
 impl fmt::Debug for VariableRegisters {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut dbg = f.debug_struct("VariableRegisteres");
-        for (index, value) in self.registers.iter().enumerate() {
-            let reg_name = format!("V{:1X}", index).to_string();
+        for (register, value) in self.iter() {
+            let reg_name = format!("V{:1X}", *register);
             dbg.field(&reg_name, &format_args!("0x{:0>2X}", value));
         }
 
@@ -52,50 +177,473 @@ impl fmt::Debug for VariableRegisters {
     }
 }
 
+/// Prints the registers on one line, e.g. `V0:00 V1:FF ... VF:01`
+impl fmt::Display for VariableRegisters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registers = self
+            .iter()
+            .map(|(register, value)| format!("V{:X}:{:0>2X}", *register, value))
+            .collect::<Vec<_>>();
+        write!(f, "{}", registers.join(" "))
+    }
+}
+
+/// Snapshot of the execution state of a cpu.
+/// The RPL user flags and the random number generator are not part of it.
+#[derive(Clone)]
+pub struct CpuState {
+    display: Display,
+    program_counter: MemoryAddress,
+    index: MemoryAddress,
+    stack: Vec<MemoryAddress>,
+    delay_timer: Timer,
+    sound_timer: Timer,
+    registers: VariableRegisters,
+    memory: Memory,
+    rom_checksum: Option<u32>,
+    tick_count: u64,
+}
+
+impl CpuState {
+    /// Collects the changes between two states
+    pub fn diff(before: &CpuState, after: &CpuState) -> CpuStateDiff {
+        let changed_registers = before
+            .registers
+            .iter()
+            .zip(after.registers.iter())
+            .filter(|((_, value_before), (_, value_after))| value_before != value_after)
+            .map(|((register, value_before), (_, value_after))| {
+                (register, value_before, value_after)
+            })
+            .collect();
+
+        let changed_index = (*before.index != *after.index).then_some((before.index, after.index));
+        let changed_stack_depth = (before.stack.len() != after.stack.len())
+            .then_some((before.stack.len(), after.stack.len()));
+
+        CpuStateDiff {
+            changed_registers,
+            pc_before: before.program_counter,
+            pc_after: after.program_counter,
+            changed_index,
+            changed_stack_depth,
+            changed_memory: before.memory.compare(&after.memory),
+            display_changed: before.display != after.display,
+        }
+    }
+}
+
+/// Changes between two consecutive cpu states
+#[derive(Clone)]
+pub struct CpuStateDiff {
+    /// Register with its value before and after
+    pub changed_registers: Vec<(U4, u8, u8)>,
+    pub pc_before: MemoryAddress,
+    pub pc_after: MemoryAddress,
+    pub changed_index: Option<(MemoryAddress, MemoryAddress)>,
+    pub changed_stack_depth: Option<(usize, usize)>,
+    /// Memory address with its value before and after
+    pub changed_memory: Vec<(MemoryAddress, u8, u8)>,
+    pub display_changed: bool,
+}
+
+impl CpuStateDiff {
+    /// Describes every change in a single line, e.g. "V3: 0x00 → 0x42"
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "PC: 0x{:0>3X} → 0x{:0>3X}",
+            *self.pc_before, *self.pc_after
+        )];
+
+        for (register, before, after) in self.changed_registers.iter() {
+            lines.push(format!(
+                "V{:X}: 0x{:0>2X} → 0x{:0>2X}",
+                **register, before, after
+            ));
+        }
+
+        if let Some((before, after)) = self.changed_index {
+            lines.push(format!("I: 0x{:0>3X} → 0x{:0>3X}", *before, *after));
+        }
+
+        if let Some((before, after)) = self.changed_stack_depth {
+            lines.push(format!("Stack depth: {} → {}", before, after));
+        }
+
+        for (address, before, after) in self.changed_memory.iter() {
+            lines.push(format!(
+                "[0x{:0>3X}]: 0x{:0>2X} → 0x{:0>2X}",
+                **address, before, after
+            ));
+        }
+
+        if self.display_changed {
+            lines.push("Display changed".to_string());
+        }
+
+        lines
+    }
+}
+
+/// Reason why `Cpu::run_until_halt` stopped
+#[derive(Debug)]
+pub enum HaltReason {
+    /// The program ended in a jump to its own address
+    InfiniteLoop {
+        address: MemoryAddress,
+    },
+    Error(anyhow::Error),
+    /// The program executed the SUPER-CHIP exit instruction 00FD
+    Exited,
+    /// The program was still running after the given number of cycles
+    CycleLimit(u64),
+}
+
+/// Creates a cpu from optional parts, the missing parts use the same defaults as `Cpu::default`
+pub struct CpuBuilder<TKeypad: Keypad + Default> {
+    rom: Option<Rom>,
+    keypad: Option<TKeypad>,
+    config: CpuConfig,
+    seed: Option<u64>,
+}
+
+impl<T: Keypad + Default> Default for CpuBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Keypad + Default> CpuBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            rom: None,
+            keypad: None,
+            config: CpuConfig::default(),
+            seed: None,
+        }
+    }
+
+    pub fn with_rom(mut self, rom: Rom) -> Self {
+        self.rom = Some(rom);
+        self
+    }
+
+    pub fn with_keypad(mut self, keypad: T) -> Self {
+        self.keypad = Some(keypad);
+        self
+    }
+
+    /// Replaces the whole config, including a stack limit set before
+    pub fn with_config(mut self, config: CpuConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_stack_limit(mut self, max_stack_depth: usize) -> Self {
+        self.config.max_stack_depth = max_stack_depth;
+        self
+    }
+
+    /// Fails if the rom does not fit into the memory
+    pub fn build(self) -> Result<Cpu<T>> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut cpu = Cpu::with_seed_and_config(seed, self.config);
+        if let Some(rom) = self.rom {
+            cpu.rom_checksum = Some(rom.checksum());
+            cpu.memory = Memory::from_rom(rom)?;
+        }
+        if let Some(keypad) = self.keypad {
+            cpu.keypad = keypad;
+        }
+
+        Ok(cpu)
+    }
+}
+
 pub struct Cpu<TKeypad: Keypad + Default> {
-    pub display: Display,
-    pub program_counter: MemoryAddress,
-    pub index: MemoryAddress,
-    pub stack: Vec<MemoryAddress>,
+    display: Display,
+    program_counter: MemoryAddress,
+    index: MemoryAddress,
+    stack: Vec<MemoryAddress>,
     pub delay_timer: Timer,
-    pub sound_timer: u8,
-    pub registers: VariableRegisters,
-    pub memory: Memory,
+    pub sound_timer: Timer,
+    registers: VariableRegisters,
+    memory: Memory,
+    /// SUPER-CHIP user flags, kept across resets
+    pub rpl_flags: [u8; RPL_FLAG_COUNT],
+    config: CpuConfig,
+    /// Checksum of the loaded rom, if the cpu was created from one
+    rom_checksum: Option<u32>,
+    /// Number of executed ticks since the last reset
+    tick_count: u64,
+    /// Instruction of the last tick, also set if its execution failed
+    last_instruction: Option<Instruction>,
+    /// Set by 00FD, further ticks do nothing
+    exited: bool,
     keypad: TKeypad,
+    seed: u64,
+    rng: Box<dyn RngCore>,
 }
 
 impl<T: Keypad + Default> Default for Cpu<T> {
     fn default() -> Self {
+        Self::with_seed(rand::random())
+    }
+}
+
+impl<T: Keypad + Default> Cpu<T> {
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_seed_and_config(seed, CpuConfig::default())
+    }
+
+    pub fn default_with_config(config: CpuConfig) -> Self {
+        Self::with_seed_and_config(rand::random(), config)
+    }
+
+    fn with_seed_and_config(seed: u64, config: CpuConfig) -> Self {
+        let mut display = Display::new();
+        display.wrap_sprites = config.wrap_sprites;
+
         Cpu {
-            display: Display::new(),
+            display,
             program_counter: MEMORY_START,
             index: MemoryAddress::from_u16(0),
             stack: Vec::new(),
-            delay_timer: Timer::with_cpu_rate(INSTRUCTION_RATE),
-            sound_timer: 0,
+            delay_timer: Timer::with_cpu_rate(config.cpu_rate_hz),
+            sound_timer: Timer::with_cpu_rate(config.cpu_rate_hz),
             registers: VariableRegisters::new(),
             memory: Memory::new(),
+            rpl_flags: [0; RPL_FLAG_COUNT],
+            config,
+            rom_checksum: None,
+            tick_count: 0,
+            last_instruction: None,
+            exited: false,
             keypad: T::default(),
+            seed,
+            rng: Box::new(SmallRng::seed_from_u64(seed)),
         }
     }
-}
 
-impl<T: Keypad + Default> Cpu<T> {
     pub fn from_rom(rom: Rom) -> Result<Self> {
-        let cpu = Cpu {
-            memory: Memory::from_rom(rom)?,
-            ..Default::default()
+        Self::from_rom_with_config(rom, CpuConfig::default())
+    }
+
+    pub fn from_rom_with_config(rom: Rom, config: CpuConfig) -> Result<Self> {
+        CpuBuilder::new().with_rom(rom).with_config(config).build()
+    }
+
+    /// Resets the execution state while keeping the memory, the RPL user flags and the configuration.
+    /// The random number generator is reseeded with the current seed.
+    pub fn reset(&mut self) {
+        *self = Cpu {
+            memory: std::mem::take(&mut self.memory),
+            rpl_flags: self.rpl_flags,
+            rom_checksum: self.rom_checksum,
+            ..Self::with_seed_and_config(self.seed, self.config)
         };
+    }
 
-        Ok(cpu)
+    pub fn config(&self) -> &CpuConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: CpuConfig) {
+        self.delay_timer.set_cpu_rate(config.cpu_rate_hz);
+        self.sound_timer.set_cpu_rate(config.cpu_rate_hz);
+        self.display.wrap_sprites = config.wrap_sprites;
+        self.config = config;
+    }
+
+    pub fn set_keypad(&mut self, keypad: T) {
+        self.keypad = keypad;
+    }
+
+    pub fn set_cpu_rate(&mut self, cpu_rate: u64) {
+        self.set_config(CpuConfig {
+            cpu_rate_hz: cpu_rate,
+            ..self.config
+        });
+    }
+
+    pub fn save_state(&self) -> CpuState {
+        CpuState {
+            display: self.display.clone(),
+            program_counter: self.program_counter,
+            index: self.index,
+            stack: self.stack.clone(),
+            delay_timer: self.delay_timer.clone(),
+            sound_timer: self.sound_timer.clone(),
+            registers: self.registers.clone(),
+            memory: self.memory.clone(),
+            rom_checksum: self.rom_checksum,
+            tick_count: self.tick_count,
+        }
+    }
+
+    /// Restores a saved state. Fails if the state was saved while a different rom was loaded.
+    pub fn load_state(&mut self, state: CpuState) -> Result<()> {
+        if state.rom_checksum != self.rom_checksum {
+            return Err(anyhow!(
+                "Cpu state belongs to a different rom ({}), the loaded rom is {}",
+                format_checksum(state.rom_checksum),
+                format_checksum(self.rom_checksum)
+            ));
+        }
+
+        self.display = state.display;
+        self.program_counter = state.program_counter;
+        self.index = state.index;
+        self.stack = state.stack;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.registers = state.registers;
+        self.memory = state.memory;
+        self.tick_count = state.tick_count;
+        self.last_instruction = None;
+        self.exited = false;
+        Ok(())
+    }
+
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    pub fn last_instruction(&self) -> Option<Instruction> {
+        self.last_instruction
+    }
+
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Return addresses of the called subroutines, the innermost call last
+    pub fn stack_entries(&self) -> &[MemoryAddress] {
+        &self.stack
+    }
+
+    /// Depth, return address and decoded call site of every stack entry, the innermost call first
+    pub fn stack_trace(&self) -> Vec<(usize, MemoryAddress, Result<Instruction>)> {
+        self.stack
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(depth, &return_address)| {
+                (
+                    depth,
+                    return_address,
+                    self.call_site_instruction(return_address),
+                )
+            })
+            .collect()
+    }
+
+    fn call_site_instruction(&self, return_address: MemoryAddress) -> Result<Instruction> {
+        let call_site = return_address.checked_sub(2).ok_or_else(|| {
+            anyhow!(
+                "No instruction before the return address 0x{:0>3X}",
+                *return_address
+            )
+        })?;
+        let bytes = self
+            .memory
+            .read_slice(MemoryAddress::from_u16(call_site), 2)?;
+        Instruction::try_from_bytes(bytes[0], bytes[1])
+    }
+
+    pub fn rom_checksum(&self) -> Option<u32> {
+        self.rom_checksum
+    }
+
+    pub fn registers(&self) -> &VariableRegisters {
+        &self.registers
+    }
+
+    pub fn get_register(&self, register: U4) -> u8 {
+        self.registers.get_value(register)
+    }
+
+    pub fn set_register(&mut self, register: U4, value: u8) {
+        self.registers.set_value(register, value);
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.program_counter.set(value);
+    }
+
+    pub fn set_index(&mut self, value: u16) {
+        self.index.set(value);
+    }
+
+    pub fn pc(&self) -> u16 {
+        *self.program_counter
+    }
+
+    pub fn pc_address(&self) -> MemoryAddress {
+        self.program_counter
+    }
+
+    pub fn index_value(&self) -> u16 {
+        *self.index
+    }
+
+    pub fn index_address(&self) -> MemoryAddress {
+        self.index
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Reads `len` bytes starting at the address, failing if the range exceeds the memory
+    pub fn inspect_memory(&self, address: MemoryAddress, len: usize) -> Result<&[u8]> {
+        self.memory.read_slice(address, len)
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Box::new(SmallRng::seed_from_u64(seed));
     }
 
     pub fn tick(&mut self) -> Result<()> {
+        if self.exited {
+            return Ok(());
+        }
+
+        self.tick_count += 1;
+        self.last_instruction = None;
+        self.keypad.tick();
         let instruction = self
             .fetch_instruction()
             .with_context(|| "Error while fetching new instruction")?;
 
         self.delay_timer.tick();
+        self.sound_timer.tick();
         self.program_counter.increment();
 
         self.handle_instruction(instruction)
@@ -104,11 +652,60 @@ impl<T: Keypad + Default> Cpu<T> {
         Ok(())
     }
 
+    /// Runs up to `cycles` ticks and returns the number of completed ticks together with the error
+    /// that stopped the execution early. Invalid instructions only stop it if the lenient mode is off.
+    /// The execution also stops early without an error once the rom exits.
+    pub fn run_for_cycles(&mut self, cycles: u64) -> (u64, Option<anyhow::Error>) {
+        for completed in 0..cycles {
+            if let Err(err) = self.tick() {
+                return (completed, Some(err));
+            }
+            if self.exited {
+                return (completed + 1, None);
+            }
+        }
+
+        (cycles, None)
+    }
+
+    /// Runs the program until it ends in a jump to itself, fails or exceeds `max_cycles`
+    pub fn run_until_halt(&mut self, max_cycles: u64) -> HaltReason {
+        for _ in 0..max_cycles {
+            let address = self.program_counter;
+            if let Err(err) = self.tick() {
+                return HaltReason::Error(err);
+            }
+            if self.exited {
+                return HaltReason::Exited;
+            }
+
+            if let Some(Instruction::Jump(target)) = self.last_instruction {
+                if target == *address {
+                    return HaltReason::InfiniteLoop { address };
+                }
+            }
+        }
+
+        HaltReason::CycleLimit(max_cycles)
+    }
+
+    /// Executes a single instruction and passes the resulting state to the callback
+    pub fn tick_with_callback<F: FnOnce(&Self)>(&mut self, callback: F) -> Result<()> {
+        self.tick()?;
+        callback(self);
+
+        Ok(())
+    }
+
     fn handle_instruction(&mut self, instruction: Instruction) -> Result<()> {
+        self.last_instruction = Some(instruction);
         match instruction {
             Instruction::AddRegisterToIndex { register } => {
                 let value = self.registers.get_value(register);
                 self.index = self.index.add(value as u16);
+                if self.config.compatibility_mode.sets_flag_on_index_overflow() {
+                    self.registers.set_flag(*self.index > 0xFFF);
+                }
             }
             Instruction::AddValue { register, value } => self.registers.add_value(register, value),
             Instruction::AddRegisters {
@@ -119,9 +716,7 @@ impl<T: Keypad + Default> Cpu<T> {
                 let value2 = self.registers.get_value(register2);
                 let (result, did_overflow) = value1.overflowing_add(value2);
                 self.registers.set_value(register1, result);
-
-                let flag_value = if did_overflow { 1 } else { 0 };
-                self.registers.set_value(U4::new(0xF), flag_value);
+                self.registers.set_flag(did_overflow);
             }
             Instruction::And {
                 register1,
@@ -130,13 +725,33 @@ impl<T: Keypad + Default> Cpu<T> {
                 let value1 = self.registers.get_value(register1);
                 let value2 = self.registers.get_value(register2);
                 self.registers.set_value(register1, value1 & value2);
-                self.registers.set_value(U4::new(0xF), 0);
+                if self.config.reset_vf_on_logic {
+                    self.registers.set_flag(false);
+                }
             }
             Instruction::CallSubroutine(addr) => {
+                if self.stack.len() >= self.config.max_stack_depth {
+                    return Err(anyhow!(
+                        "Stack overflow, exceeded the maximum depth of {}",
+                        self.config.max_stack_depth
+                    ));
+                }
                 self.stack.push(self.program_counter);
                 self.program_counter = addr;
             }
             Instruction::ClearScreen => self.display.clear(),
+            Instruction::EnableHighResolution => self.display.set_hires(true),
+            Instruction::DisableHighResolution => self.display.set_hires(false),
+            Instruction::ScrollDown { rows } => self.display.scroll_down(*rows as usize),
+            Instruction::ScrollRight => self.display.scroll_right(SCROLL_DISTANCE),
+            Instruction::ScrollLeft => self.display.scroll_left(SCROLL_DISTANCE),
+            Instruction::ExitEmulator => self.exited = true,
+            Instruction::SetPlane { planes } => self.display.set_plane(*planes),
+            Instruction::Nop(_) => {}
+            Instruction::DrawExtended {
+                register1,
+                register2,
+            } => self.handle_draw_extended_instruction(register1, register2)?,
             Instruction::Draw {
                 register1,
                 register2,
@@ -144,7 +759,13 @@ impl<T: Keypad + Default> Cpu<T> {
             } => self.handle_draw_instruction(register1, register2, sprite_length)?,
             Instruction::Jump(address) => self.program_counter.set(address),
             Instruction::JumpWithOffset(address) => {
-                let offset = self.registers.get_value(U4::new(0));
+                let offset_register = if self.config.compatibility_mode.jumps_with_register_offset()
+                {
+                    U4::new((address >> 8) as u8 & 0xF)
+                } else {
+                    U4::new(0)
+                };
+                let offset = self.registers.get_value(offset_register);
                 self.program_counter.set(address + offset as u16);
             }
             Instruction::LoadDelayTimer { register } => {
@@ -155,6 +776,16 @@ impl<T: Keypad + Default> Cpu<T> {
                 let value = U4::new(value & 0b00001111);
                 self.index = self.memory.get_address_for_font(value);
             }
+            Instruction::LoadLong { address } => {
+                self.index.set(address);
+                // skip the address word
+                self.program_counter.increment();
+            }
+            Instruction::LoadLargeFont { register } => {
+                let value = self.registers.get_value(register);
+                let value = U4::new(value & 0b00001111);
+                self.index = self.memory.get_address_for_large_font(value)?;
+            }
             Instruction::LoadRegisterFromKeyPress { register } => {
                 let Some(value) = self.keypad.get_pressed_key() else {
                     self.program_counter.decrement();
@@ -174,7 +805,9 @@ impl<T: Keypad + Default> Cpu<T> {
                     let register = U4::new(idx as u8);
                     self.registers.set_value(register, *byte);
                 }
-                self.index = self.index.add(count as u16);
+                self.index = self
+                    .index
+                    .add(self.config.compatibility_mode.index_increment(register));
             }
             Instruction::LoadRegisterFromRegister {
                 register1,
@@ -183,8 +816,15 @@ impl<T: Keypad + Default> Cpu<T> {
                 let value = self.registers.get_value(register2);
                 self.registers.set_value(register1, value);
             }
+            Instruction::LoadRplFlags { register } => {
+                for idx in 0..=*register {
+                    let register = U4::new(idx);
+                    self.registers
+                        .set_value(register, self.rpl_flags[usize::from(register)]);
+                }
+            }
             Instruction::LoadSoundTimer { register } => {
-                self.sound_timer = self.registers.get_value(register);
+                self.sound_timer.set(self.registers.get_value(register));
             }
             Instruction::Or {
                 register1,
@@ -193,10 +833,12 @@ impl<T: Keypad + Default> Cpu<T> {
                 let value1 = self.registers.get_value(register1);
                 let value2 = self.registers.get_value(register2);
                 self.registers.set_value(register1, value1 | value2);
-                self.registers.set_value(U4::new(0xF), 0);
+                if self.config.reset_vf_on_logic {
+                    self.registers.set_flag(false);
+                }
             }
             Instruction::Random { register, mask } => {
-                let rnd = rand::thread_rng().gen::<u8>();
+                let rnd = self.rng.gen::<u8>();
                 self.registers.set_value(register, rnd & mask);
             }
             Instruction::Return => {
@@ -205,33 +847,49 @@ impl<T: Keypad + Default> Cpu<T> {
                 })?;
                 self.program_counter = address;
             }
+            Instruction::SaveRplFlags { register } => {
+                for idx in 0..=*register {
+                    let register = U4::new(idx);
+                    self.rpl_flags[usize::from(register)] = self.registers.get_value(register);
+                }
+            }
             Instruction::SetIndex(new_index) => self.index.set(new_index),
             Instruction::SetValue { register, value } => self.registers.set_value(register, value),
             Instruction::ShiftLeft { register1, register2 } => {
-                let value = self.registers.get_value(register2);
+                let source = if self.config.compatibility_mode.shifts_in_place() {
+                    register1
+                } else {
+                    register2
+                };
+                let value = self.registers.get_value(source);
                 self.registers.set_value(register1, value << 1);
-                self.registers.set_value(U4::new(0xF), value >> 7);
+                self.registers.set_flag(value & 0x80 != 0);
             }
             Instruction::ShiftRight { register1, register2 } => {
-                let value = self.registers.get_value(register2);
+                let source = if self.config.compatibility_mode.shifts_in_place() {
+                    register1
+                } else {
+                    register2
+                };
+                let value = self.registers.get_value(source);
                 self.registers.set_value(register1, value >> 1);
-                self.registers.set_value(U4::new(0xF), value & 1);
+                self.registers.set_flag(value & 1 != 0);
             }
             Instruction::SkipIfEqual { register, value } => {
                 if self.registers.get_value(register) == value {
-                    self.program_counter.increment();
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SkipIfKeyPressed { register } => {
                 let value = self.registers.get_value(register);
                 if self.keypad.is_key_down(value) {
-                    self.program_counter.increment();
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SkipIfKeyNotPressed { register } => {
                 let value = self.registers.get_value(register);
                 if !self.keypad.is_key_down(value) {
-                    self.program_counter.increment();
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SkipIfEqualRegisters {
@@ -241,12 +899,12 @@ impl<T: Keypad + Default> Cpu<T> {
                 let value1 = self.registers.get_value(register1);
                 let value2 = self.registers.get_value(register2);
                 if value1 == value2 {
-                    self.program_counter.increment();
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SkipNotEqualByte { register, value } => {
                 if self.registers.get_value(register) != value {
-                    self.program_counter.increment();
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SkipNotEqualRegisters {
@@ -256,7 +914,7 @@ impl<T: Keypad + Default> Cpu<T> {
                 let value1 = self.registers.get_value(register1);
                 let value2 = self.registers.get_value(register2);
                 if value1 != value2 {
-                    self.program_counter.increment();
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SubRegisters {
@@ -280,17 +938,17 @@ impl<T: Keypad + Default> Cpu<T> {
                 let d0 = value / 100;
                 let d1 = (value % 100) / 10;
                 let d2 = value % 10;
-                self.memory[self.index] = d0;
-                self.memory[self.index.add(1)] = d1;
-                self.memory[self.index.add(2)] = d2;
+                self.memory.write_slice_checked(self.index, &[d0, d1, d2])?;
             }
             Instruction::WriteRegistersToMemory { register } => {
                 let bytes = (0..=*register)
                     .map(|r| U4::new(r))
                     .map(|r| self.registers.get_value(r))
                     .collect::<Vec<_>>();
-                self.memory.write_slice(self.index, &bytes)?;
-                self.index = self.index.add(*register as u16 + 1);
+                self.memory.write_slice_checked(self.index, &bytes)?;
+                self.index = self
+                    .index
+                    .add(self.config.compatibility_mode.index_increment(register));
             }
             Instruction::Xor {
                 register1,
@@ -299,23 +957,50 @@ impl<T: Keypad + Default> Cpu<T> {
                 let value1 = self.registers.get_value(register1);
                 let value2 = self.registers.get_value(register2);
                 self.registers.set_value(register1, value1 ^ value2);
-                self.registers.set_value(U4::new(0xF), 0);
+                if self.config.reset_vf_on_logic {
+                    self.registers.set_flag(false);
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Skips the next instruction, including the address word of a long index load
+    fn skip_next_instruction(&mut self) {
+        let next_instruction = self
+            .memory
+            .read_slice(self.program_counter, 2)
+            .ok()
+            .map(|bytes| join_bytes(bytes[0], bytes[1]));
+        if next_instruction == Some(LOAD_LONG_OPCODE) {
+            self.program_counter.increment();
+        }
+        self.program_counter.increment();
+    }
+
     fn handle_sub(&mut self, lhs: u8, rhs: u8, target_register: U4) {
         let result = lhs.wrapping_sub(rhs);
         self.registers.set_value(target_register, result);
-
-        let flag_value = if lhs >= rhs { 1 } else { 0 };
-        self.registers.set_value(U4::new(0xF), flag_value);
+        self.registers.set_flag(lhs >= rhs);
     }
 
     fn fetch_instruction(&mut self) -> Result<Instruction> {
-        let instruction = self.memory.read_instruction(self.program_counter);
+        let instruction = self.memory.read_instruction_checked(self.program_counter)?;
+        if instruction == LOAD_LONG_OPCODE {
+            let operand = self
+                .memory
+                .read_slice(self.program_counter.add(2), 2)
+                .with_context(|| "Missing address of the long index load")?;
+            return Ok(Instruction::LoadLong {
+                address: join_bytes(operand[0], operand[1]),
+            });
+        }
+
+        if self.config.lenient {
+            return Ok(Instruction::from_u16_lenient(instruction));
+        }
+
         let instruction = Instruction::try_from_u16(instruction).with_context(|| {
             format!("Error occoured at address 0x{:0>4X}", *self.program_counter)
         })?;
@@ -331,20 +1016,52 @@ impl<T: Keypad + Default> Cpu<T> {
     ) -> Result<()> {
         let x_pos = self.registers.get_value(x_register);
         let y_pos = self.registers.get_value(y_register);
-        let sprite = self
-            .memory
-            .read_slice(self.index, usize::from(sprite_length))?;
-        self.display.draw(x_pos, y_pos, sprite);
+        let sprite_length = usize::from(sprite_length) * self.display.selected_plane_count();
+        let sprite = self.memory.read_slice(self.index, sprite_length)?;
+        let has_turned_off_pixel = self.display.draw(x_pos, y_pos, sprite);
+        self.registers.set_flag(has_turned_off_pixel);
+
+        Ok(())
+    }
+
+    /// Draws a 16x16 sprite of 2 byte rows in the high resolution mode and in the low resolution mode
+    /// of the SUPER-CHIP mode. Otherwise nothing is drawn, like a regular draw instruction with a
+    /// sprite length of 0.
+    fn handle_draw_extended_instruction(&mut self, x_register: U4, y_register: U4) -> Result<()> {
+        let draws_sprite = self.display.is_hires()
+            || self
+                .config
+                .compatibility_mode
+                .draws_extended_sprites_in_low_resolution();
+        if !draws_sprite {
+            self.registers.set_flag(false);
+            return Ok(());
+        }
+
+        let x_pos = self.registers.get_value(x_register);
+        let y_pos = self.registers.get_value(y_register);
+        let sprite_length = 32 * self.display.selected_plane_count();
+        let sprite = self.memory.read_slice(self.index, sprite_length)?;
+        let has_turned_off_pixel = self.display.draw_extended(x_pos, y_pos, sprite);
+        self.registers.set_flag(has_turned_off_pixel);
 
         Ok(())
     }
 }
 
+/// Formats an optional rom checksum as hex, or "no rom" if there is none
+pub fn format_checksum(checksum: Option<u32>) -> String {
+    match checksum {
+        Some(checksum) => format!("0x{:0>8X}", checksum),
+        None => "no rom".to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         bits::{join_nibbles, join_to_u8, split_instruction, split_u16, split_u8},
-        keypad::MockKeypad,
+        keypad::{MockKeypad, ReplayKeypad},
     };
 
     use super::*;
@@ -356,7 +1073,7 @@ mod tests {
         let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
 
         println!("{:0>4X?}", cpu.program_counter);
-        println!("{:X?}", cpu.memory.read_slice(MEMORY_START, 4));
+        println!("{:X?}", cpu.inspect_memory(MEMORY_START, 4));
         cpu.tick().unwrap();
 
         assert_eq!(usize::from(cpu.index), 0x234);
@@ -434,23 +1151,25 @@ mod tests {
         let mut cpu =
             Cpu::<MockKeypad>::from_rom(Rom::from_raw_instructions(&raw_instructions)).unwrap();
 
-        let original_address = *cpu.program_counter;
+        let original_address = cpu.pc();
 
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x345, *cpu.program_counter,
+            0x345,
+            cpu.pc(),
             "Program counter has not been set to {:X}, actual {:X}",
-            0x345, *cpu.program_counter
+            0x345,
+            cpu.pc()
         );
         assert_eq!(
             1,
-            cpu.stack.len(),
+            cpu.stack_depth(),
             "Expected one address to be pushed to the stack"
         );
         assert_eq!(
             original_address + 2,
-            *cpu.stack[0],
+            *cpu.stack_entries()[0],
             "Address pushed to the stack is wrong"
         );
     }
@@ -471,20 +1190,20 @@ mod tests {
 
             cpu.tick().unwrap();
 
-            let original_address = *cpu.program_counter;
+            let original_address = cpu.pc();
             cpu.tick().unwrap();
             assert_eq!(
                 original_address + 2,
-                *cpu.program_counter,
+                cpu.pc(),
                 "{:X}: Expected PC to increment normally and not skip ahead",
                 register
             );
 
-            let original_address = *cpu.program_counter;
+            let original_address = cpu.pc();
             cpu.tick().unwrap();
             assert_eq!(
                 original_address + 4,
-                *cpu.program_counter,
+                cpu.pc(),
                 "{:X}: Expected PC to increment twice and skip one instruction",
                 register
             );
@@ -507,20 +1226,20 @@ mod tests {
 
             cpu.tick().unwrap();
 
-            let original_address = *cpu.program_counter;
+            let original_address = cpu.pc();
             cpu.tick().unwrap();
             assert_eq!(
                 original_address + 2,
-                *cpu.program_counter,
+                cpu.pc(),
                 "{:X}: Expected PC to increment normally and not skip ahead",
                 register
             );
 
-            let original_address = *cpu.program_counter;
+            let original_address = cpu.pc();
             cpu.tick().unwrap();
             assert_eq!(
                 original_address + 4,
-                *cpu.program_counter,
+                cpu.pc(),
                 "{:X}: Expected PC to increment twice and skip one instruction",
                 register
             );
@@ -557,7 +1276,7 @@ mod tests {
 
             assert_eq!(
                 0x200 + current_register as u16 + 1,
-                *cpu.index,
+                cpu.index_value(),
                 "Index register is set to the address of the last written byte"
             );
 
@@ -577,6 +1296,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn last_instruction_is_recorded_per_tick() {
+        let instructions = vec![0xD015, 0x00EE];
+        let mut cpu =
+            Cpu::<MockKeypad>::from_rom(Rom::from_raw_instructions(&instructions)).unwrap();
+        assert!(cpu.last_instruction().is_none());
+
+        cpu.tick().unwrap();
+        assert!(
+            cpu.last_instruction()
+                == Some(Instruction::Draw {
+                    register1: U4::new(0),
+                    register2: U4::new(1),
+                    sprite_length: U4::new(5),
+                })
+        );
+
+        assert!(cpu.tick().is_err());
+        assert!(cpu.last_instruction() == Some(Instruction::Return));
+
+        cpu.reset();
+        assert!(cpu.last_instruction().is_none());
+    }
+
     #[test]
     fn correctly_handles_fx29_load_font() {
         let instructions = vec![
@@ -596,17 +1339,145 @@ mod tests {
         cpu.tick().unwrap();
 
         cpu.tick().unwrap();
-        assert_eq!(0x4B, *cpu.index);
+        assert_eq!(0x4B, cpu.index_value());
 
         cpu.tick().unwrap();
-        assert_eq!(0x0, *cpu.index);
+        assert_eq!(0x0, cpu.index_value());
 
         cpu.tick().unwrap();
-        assert_eq!(0x19, *cpu.index);
+        assert_eq!(0x19, cpu.index_value());
     }
 
     #[test]
-    fn correctly_handle_fx33_store_decimal_conversion() {
+    fn fx29_loads_glyphs_from_custom_font() {
+        let mut instructions = Vec::new();
+        for digit in 0..16 {
+            instructions.push(0x6000 + digit); // load digit into V0
+            instructions.push(0xF029); // Load font using V0
+        }
+
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        let custom_font: [u8; 80] = std::array::from_fn(|index| 0xFF - index as u8);
+        cpu.memory_mut().load_custom_font(&custom_font).unwrap();
+
+        for glyph in custom_font.chunks(5) {
+            cpu.tick().unwrap();
+            cpu.tick().unwrap();
+            let address = MemoryAddress::from_u16(cpu.index_value());
+            assert_eq!(glyph, cpu.inspect_memory(address, 5).unwrap());
+        }
+    }
+
+    #[test]
+    fn correctly_handles_fx30_load_large_font() {
+        let instructions = vec![
+            0x6003, // load 3 into V0
+            0x610A, // load A into V1
+            0xF030, // Load large font using V0
+            0xF130, // Load large font using V1
+        ];
+
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        cpu.tick().unwrap();
+        assert_eq!(0x50 + 3 * 10, cpu.index_value());
+
+        assert!(cpu.tick().is_err());
+    }
+
+    #[test]
+    fn correctly_handles_f000_load_long() {
+        let instructions = vec![
+            0xF000, 0xBEEF, // load 0xBEEF into I
+            0x6001, // load 1 into V0
+        ];
+
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        cpu.tick().unwrap();
+        assert_eq!(0xBEEF, cpu.index_value());
+        assert_eq!(0x204, cpu.pc());
+
+        cpu.tick().unwrap();
+        assert_eq!(0x01, cpu.registers.get_value(U4::new(0)));
+    }
+
+    #[test]
+    fn fx1e_wraps_index_after_load_long() {
+        let instructions = vec![
+            0xF000, 0xFFFF, // load 0xFFFF into I
+            0x6001, // load 1 into V0
+            0xF01E, // add V0 to I
+        ];
+
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        for _ in 0..3 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(0x0000, cpu.index_value());
+    }
+
+    #[test]
+    fn fx1e_sets_flag_on_index_overflow_in_super_chip_mode() {
+        let instructions = vec![
+            0xAFFE, // set I to 0xFFE
+            0x6001, // load 1 into V0
+            0xF01E, // add V0 to I
+            0xF01E, // add V0 to I
+        ];
+
+        for (mode, flag_after_add, flag_after_overflow) in [
+            (CompatibilityMode::Chip8, 0x22, 0x22),
+            (CompatibilityMode::SuperChip, 0, 1),
+        ] {
+            let rom = Rom::from_raw_instructions(&instructions);
+            let config = CpuConfig::for_mode(mode);
+            let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+            cpu.registers.set_value(U4::new(0xF), 0x22);
+
+            for _ in 0..3 {
+                cpu.tick().unwrap();
+            }
+            assert_eq!(flag_after_add, cpu.registers.get_value(U4::new(0xF)));
+
+            cpu.tick().unwrap();
+            assert_eq!(0x1000, cpu.index_value());
+            assert_eq!(flag_after_overflow, cpu.registers.get_value(U4::new(0xF)));
+        }
+    }
+
+    #[test]
+    fn skip_instructions_skip_whole_load_long() {
+        let skips = [0x3000, 0x4001, 0x5010, 0xE0A1];
+        for skip in skips {
+            let instructions = vec![
+                skip, // skip the long index load
+                0xF000, 0xBEEF, // load 0xBEEF into I
+                0x6001, // load 1 into V0
+            ];
+
+            let rom = Rom::from_raw_instructions(&instructions);
+            let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+            cpu.tick().unwrap();
+            assert_eq!(0x206, cpu.pc(), "Wrong skip for 0x{:0>4X}", skip);
+
+            cpu.tick().unwrap();
+            assert_eq!(0x01, cpu.get_register(U4::new(0)));
+            assert_eq!(0x000, cpu.index_value());
+        }
+    }
+
+    #[test]
+    fn correctly_handle_fx33_store_decimal_conversion() {
         let instructions = vec![
             0x60FE, // store 254 in V0
             0xA500, // set index to 0x500
@@ -620,7 +1491,7 @@ mod tests {
         cpu.tick().unwrap();
         cpu.tick().unwrap();
 
-        let bytes = cpu.memory.read_slice(cpu.index, 3).unwrap();
+        let bytes = cpu.inspect_memory(cpu.index, 3).unwrap();
 
         assert_eq!(2, bytes[0]);
         assert_eq!(5, bytes[1]);
@@ -782,9 +1653,10 @@ mod tests {
         cpu.tick().unwrap();
         cpu.tick().unwrap();
 
-        assert_eq!(0, cpu.stack.len(), "Stack should have been empty");
+        assert_eq!(0, cpu.stack_depth(), "Stack should have been empty");
         assert_eq!(
-            0x202, *cpu.program_counter,
+            0x202,
+            cpu.pc(),
             "Program counter is at the wrong address after returning"
         );
     }
@@ -982,14 +1854,16 @@ mod tests {
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x208, *cpu.program_counter,
+            0x208,
+            cpu.pc(),
             "PC should not have skipped ahead because V1 and V2 are not equal"
         );
 
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x20C, *cpu.program_counter,
+            0x20C,
+            cpu.pc(),
             "PC should have skipped ahead because V1 and V3 are equal"
         );
     }
@@ -1007,18 +1881,32 @@ mod tests {
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x208, *cpu.program_counter,
+            0x208,
+            cpu.pc(),
             "PC should not have skipped ahead because V1 and V2 are equal"
         );
 
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x20C, *cpu.program_counter,
+            0x20C,
+            cpu.pc(),
             "PC should have skipped ahead because V1 and V3 are not equal"
         );
     }
 
+    #[test]
+    fn memory_writes_below_rom_area_fail() {
+        for write_instruction in [0xF055, 0xF033] {
+            let instructions = vec![0xA100, write_instruction];
+            let mut cpu =
+                Cpu::<MockKeypad>::from_rom(Rom::from_raw_instructions(&instructions)).unwrap();
+
+            cpu.tick().unwrap();
+            assert!(cpu.tick().is_err());
+        }
+    }
+
     #[test]
     fn correctly_handle_fx55_store_registers_to_memory() {
         let values: Vec<u8> = vec![
@@ -1042,13 +1930,12 @@ mod tests {
         instructions.iter().for_each(|_| cpu.tick().unwrap());
 
         let bytes = cpu
-            .memory
-            .read_slice(MemoryAddress::from_u16(index_start as u16), 16)
+            .inspect_memory(MemoryAddress::from_u16(index_start as u16), 16)
             .unwrap();
 
         assert_eq!(
             index_start + values.len() as u16,
-            *cpu.index,
+            cpu.index_value(),
             "Index register must be set to the address of the last loaded byte"
         );
 
@@ -1075,11 +1962,11 @@ mod tests {
         cpu.tick().unwrap();
         cpu.tick().unwrap();
 
-        assert_eq!(0x03, *cpu.index);
+        assert_eq!(0x03, cpu.index_value());
 
         cpu.tick().unwrap();
 
-        assert_eq!(0x03 + 0xA6, *cpu.index);
+        assert_eq!(0x03 + 0xA6, cpu.index_value());
     }
 
     #[test]
@@ -1091,7 +1978,7 @@ mod tests {
         cpu.tick().unwrap();
         cpu.tick().unwrap();
 
-        assert_eq!(0xA1 + 0x521, *cpu.program_counter);
+        assert_eq!(0xA1 + 0x521, cpu.pc());
     }
 
     #[test]
@@ -1103,7 +1990,7 @@ mod tests {
         cpu.tick().unwrap();
         cpu.tick().unwrap();
 
-        assert_eq!(0xA1, cpu.sound_timer);
+        assert_eq!(0xA1, cpu.sound_timer.get());
     }
 
     #[test]
@@ -1133,6 +2020,8 @@ mod tests {
 
     #[test]
     fn correctly_handle_fx0a_wait_for_key_press() {
+        assert!(Instruction::try_from_u16(0xF60A).is_ok());
+
         let instructions = vec![0xF60A];
         let rom = Rom::from_raw_instructions(&instructions);
         let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
@@ -1141,17 +2030,15 @@ mod tests {
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x200, *cpu.program_counter,
+            0x200,
+            cpu.pc(),
             "PC must not advance while waiting for input"
         );
 
-        cpu.keypad.value = Some(1);
+        cpu.keypad = MockKeypad::from_value(1);
         cpu.tick().unwrap();
 
-        assert_eq!(
-            0x202, *cpu.program_counter,
-            "PC must advance after receiving an input"
-        );
+        assert_eq!(0x202, cpu.pc(), "PC must advance after receiving an input");
         assert_eq!(
             0x1,
             cpu.registers.get_value(U4::new(6)),
@@ -1159,27 +2046,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fx0a_waits_for_replayed_key_press() {
+        let instructions = vec![0xF60A, 0xF70A];
+        let mut cpu = CpuBuilder::new()
+            .with_rom(Rom::from_raw_instructions(&instructions))
+            .with_keypad(ReplayKeypad::from_events(&[(5, Some(0xB)), (7, None)]))
+            .build()
+            .unwrap();
+
+        for _ in 0..4 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(0x200, cpu.pc());
+
+        cpu.tick().unwrap();
+        assert_eq!(0x202, cpu.pc());
+        assert_eq!(0xB, cpu.get_register(U4::new(6)));
+
+        cpu.tick().unwrap();
+        assert_eq!(0x204, cpu.pc());
+        assert_eq!(0xB, cpu.get_register(U4::new(7)));
+    }
+
     #[test]
     fn correctly_handle_fx9e_skip_if_key_pressed() {
         let instructions = vec![0x660A, 0xE69E, 0xE69E];
 
         let rom = Rom::from_raw_instructions(&instructions);
         let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
-        cpu.keypad.value = Some(0x6);
+        cpu.keypad = MockKeypad::from_value(0x6);
 
         cpu.tick().unwrap();
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x204, *cpu.program_counter,
+            0x204,
+            cpu.pc(),
             "Should not skip if the pressed key is different from the register value"
         );
 
-        cpu.keypad.value = Some(0xA);
+        cpu.keypad = MockKeypad::from_value(0xA);
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x208, *cpu.program_counter,
+            0x208,
+            cpu.pc(),
             "Should skip if the pressed key is different from the register value"
         );
     }
@@ -1190,21 +2102,23 @@ mod tests {
 
         let rom = Rom::from_raw_instructions(&instructions);
         let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
-        cpu.keypad.value = Some(0xA);
+        cpu.keypad = MockKeypad::from_value(0xA);
 
         cpu.tick().unwrap();
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x204, *cpu.program_counter,
+            0x204,
+            cpu.pc(),
             "Should not skip if the pressed key is the same as the register value"
         );
 
-        cpu.keypad.value = Some(0x6);
+        cpu.keypad = MockKeypad::from_value(0x6);
         cpu.tick().unwrap();
 
         assert_eq!(
-            0x208, *cpu.program_counter,
+            0x208,
+            cpu.pc(),
             "Should skip if the pressed key is different from the register value"
         );
     }
@@ -1276,4 +2190,760 @@ mod tests {
             "Flag register must be set to 1 at the end of the operation"
         );
     }
+
+    #[test]
+    fn correctly_handle_fx75_save_rpl_flags() {
+        let instructions = vec![0x6011, 0x6122, 0x6233, 0xF175];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        for _ in 0..instructions.len() {
+            cpu.tick().unwrap();
+        }
+
+        assert_eq!([0x11, 0x22, 0x00], cpu.rpl_flags[0..3]);
+    }
+
+    #[test]
+    fn correctly_handle_fx85_load_rpl_flags() {
+        let instructions = vec![0xF185];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        cpu.rpl_flags[0] = 0x11;
+        cpu.rpl_flags[1] = 0x22;
+        cpu.rpl_flags[2] = 0x33;
+
+        cpu.tick().unwrap();
+
+        assert_eq!(0x11, cpu.registers.get_value(U4::new(0)));
+        assert_eq!(0x22, cpu.registers.get_value(U4::new(1)));
+        assert_eq!(0x00, cpu.registers.get_value(U4::new(2)));
+    }
+
+    #[test]
+    fn rpl_flags_survive_reset() {
+        let instructions = vec![0x60AB, 0xF075];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        cpu.reset();
+
+        assert_eq!(0x200, cpu.pc());
+        assert_eq!(0x00, cpu.registers.get_value(U4::new(0)));
+        assert_eq!(0xAB, cpu.rpl_flags[0]);
+    }
+
+    #[test]
+    fn correctly_handle_cxkk_random_with_seed() {
+        let instructions = vec![0xC1FF, 0xC20F];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad> {
+            memory: Memory::from_rom(rom).unwrap(),
+            ..Cpu::with_seed(42)
+        };
+
+        let mut expected_rng = SmallRng::seed_from_u64(42);
+        let expected1 = expected_rng.gen::<u8>();
+        let expected2 = expected_rng.gen::<u8>() & 0x0F;
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        assert_eq!(expected1, cpu.registers.get_value(U4::new(1)));
+        assert_eq!(expected2, cpu.registers.get_value(U4::new(2)));
+    }
+
+    #[test]
+    fn same_seed_produces_same_random_sequence() {
+        let instructions = vec![0xC1FF, 0xC2FF, 0xC3FF];
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let rom = Rom::from_raw_instructions(&instructions);
+            let mut cpu = Cpu::<MockKeypad> {
+                memory: Memory::from_rom(rom).unwrap(),
+                ..Cpu::with_seed(42)
+            };
+            for _ in 0..instructions.len() {
+                cpu.tick().unwrap();
+            }
+            results.push(format!("{:?}", cpu.registers));
+        }
+
+        assert_eq!(results[0], results[1]);
+    }
+
+    #[test]
+    fn load_state_restores_saved_state() {
+        let instructions = vec![0x6A12, 0xA300, 0xFA33, 0x2200];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        let state = cpu.save_state();
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        cpu.load_state(state).unwrap();
+
+        assert_eq!(0x204, cpu.pc());
+        assert_eq!(0x300, cpu.index_value());
+        assert_eq!(0x12, cpu.registers.get_value(U4::new(0xA)));
+        assert_eq!(0, cpu.memory[MemoryAddress::from_u16(0x301)]);
+        assert_eq!(0, cpu.stack_depth());
+    }
+
+    #[test]
+    fn super_chip_mode_shifts_register_in_place() {
+        let instructions = vec![0x6103, 0x6280, 0x8126];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let config = CpuConfig::for_mode(CompatibilityMode::SuperChip);
+        let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+
+        for _ in 0..instructions.len() {
+            cpu.tick().unwrap();
+        }
+
+        assert_eq!(0x01, cpu.registers.get_value(U4::new(1)));
+        assert_eq!(0x01, cpu.registers.get_value(U4::new(0xF)));
+    }
+
+    #[test]
+    fn super_chip_mode_jumps_with_register_offset() {
+        let instructions = vec![0x6002, 0x6304, 0xB300];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let config = CpuConfig::for_mode(CompatibilityMode::SuperChip);
+        let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+
+        for _ in 0..instructions.len() {
+            cpu.tick().unwrap();
+        }
+
+        assert_eq!(0x304, cpu.pc());
+    }
+
+    #[test]
+    fn compatibility_mode_controls_index_increment() {
+        let modes = [
+            (CompatibilityMode::Chip8, 0x303),
+            (CompatibilityMode::Chip48, 0x302),
+            (CompatibilityMode::SuperChip, 0x300),
+        ];
+
+        for (mode, expected_index) in modes {
+            let instructions = vec![0xA300, 0xF255];
+            let rom = Rom::from_raw_instructions(&instructions);
+            let config = CpuConfig::for_mode(mode);
+            let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+
+            cpu.tick().unwrap();
+            cpu.tick().unwrap();
+
+            assert_eq!(expected_index, cpu.index_value(), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn compatibility_mode_is_parsed_from_string() {
+        assert_eq!(CompatibilityMode::Chip8, "chip8".parse().unwrap());
+        assert_eq!(CompatibilityMode::Chip48, "chip48".parse().unwrap());
+        assert_eq!(CompatibilityMode::SuperChip, "schip".parse().unwrap());
+        assert!("xochip".parse::<CompatibilityMode>().is_err());
+    }
+
+    #[test]
+    fn ibm_logo_is_drawn() {
+        let rom = Rom::from_file("./roms/ibm-logo.ch8").unwrap();
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        for _ in 0..100 {
+            cpu.tick().unwrap();
+        }
+
+        // the drawn logo is pinned as text art in the ibm logo integration test
+        assert_eq!(0xE6A2_FBDE_2D8E_AE58, cpu.display.hash());
+    }
+
+    #[test]
+    fn tick_with_callback_observes_every_program_counter() {
+        let rom = Rom::from_raw_instructions(&[
+            0x6001, // LD V0, 0x01
+            0x1206, // JP 0x206
+            0x6002, // LD V0, 0x02
+            0x6003, // LD V0, 0x03
+        ]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        let mut visited = Vec::new();
+        for _ in 0..3 {
+            cpu.tick_with_callback(|cpu| visited.push(cpu.pc()))
+                .unwrap();
+        }
+
+        assert_eq!(vec![0x202, 0x206, 0x208], visited);
+        assert_eq!(3, cpu.registers.get_value(U4::new(0)));
+    }
+
+    #[test]
+    fn stack_trace_lists_the_call_chain_innermost_first() {
+        let rom = Rom::from_raw_instructions(&[0x2204, 0x0000, 0x2300]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        cpu.memory
+            .write_instruction(MemoryAddress::from_u16(0x300), 0x00EE)
+            .unwrap();
+        assert!(cpu.stack_trace().is_empty());
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        let trace = cpu
+            .stack_trace()
+            .into_iter()
+            .map(|(depth, address, instruction)| {
+                (depth, *address, instruction.unwrap().to_string())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (0, 0x206, "CALL 0300".to_string()),
+                (1, 0x202, "CALL 0204".to_string()),
+            ],
+            trace
+        );
+
+        cpu.tick().unwrap();
+        assert_eq!(1, cpu.stack_trace().len());
+    }
+
+    #[test]
+    fn stack_trace_reports_undecodable_call_sites() {
+        let mut cpu = Cpu::<MockKeypad>::default();
+        cpu.stack.push(MemoryAddress::from_u16(0x200));
+        cpu.stack.push(MemoryAddress::from_u16(0x000));
+
+        let trace = cpu.stack_trace();
+        assert!(trace[0].2.is_err());
+        assert!(trace[1].2.is_err());
+    }
+
+    #[test]
+    fn diff_reports_changed_register() {
+        let rom = Rom::from_raw_instructions(&[0x6342]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        let before = cpu.save_state();
+        cpu.tick().unwrap();
+        let diff = CpuState::diff(&before, &cpu.save_state());
+
+        assert_eq!(1, diff.changed_registers.len());
+        let (register, value_before, value_after) = diff.changed_registers[0];
+        assert_eq!(0x3, *register);
+        assert_eq!(0x00, value_before);
+        assert_eq!(0x42, value_after);
+        assert_eq!(0x200, *diff.pc_before);
+        assert_eq!(0x202, *diff.pc_after);
+        assert!(diff.changed_index.is_none());
+        assert!(diff.changed_stack_depth.is_none());
+        assert!(!diff.display_changed);
+        assert_eq!(
+            vec!["PC: 0x200 → 0x202", "V3: 0x00 → 0x42"],
+            diff.describe()
+        );
+    }
+
+    #[test]
+    fn diff_reports_index_stack_and_display_changes() {
+        let rom = Rom::from_raw_instructions(&[0xA00A, 0xD005, 0x2206, 0x0000]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        let before = cpu.save_state();
+        for _ in 0..3 {
+            cpu.tick().unwrap();
+        }
+        let diff = CpuState::diff(&before, &cpu.save_state());
+
+        assert!(diff.changed_registers.is_empty());
+        assert_eq!(0x206, *diff.pc_after);
+        assert!(
+            matches!(diff.changed_index, Some((before, after)) if *before == 0 && *after == 0x00A)
+        );
+        assert_eq!(Some((0, 1)), diff.changed_stack_depth);
+        assert!(diff.display_changed);
+    }
+
+    #[test]
+    fn diff_reports_changed_memory() {
+        let rom = Rom::from_raw_instructions(&[0x60FE, 0xA300, 0xF033]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        let before = cpu.save_state();
+        cpu.tick().unwrap();
+        let diff = CpuState::diff(&before, &cpu.save_state());
+
+        assert_eq!(3, diff.changed_memory.len());
+        assert_eq!(
+            vec![
+                "PC: 0x204 → 0x206",
+                "[0x300]: 0x00 → 0x02",
+                "[0x301]: 0x00 → 0x05",
+                "[0x302]: 0x00 → 0x04",
+            ],
+            diff.describe()
+        );
+    }
+
+    #[test]
+    fn dxy0_draws_16x16_sprite_in_hires_mode() {
+        let mut instructions = vec![0x00FF, 0x6008, 0x6104, 0xA20C, 0xD010, 0x120A];
+        instructions.extend([0xFFFF; 16]);
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        for _ in 0..5 {
+            cpu.tick().unwrap();
+        }
+
+        assert!(cpu.display.is_hires());
+        let expected_row = 0xFFFF << (128 - 16 - 8);
+        for (y, &row) in cpu.display.hires_pixels.iter().enumerate() {
+            let expected = if (4..20).contains(&y) {
+                expected_row
+            } else {
+                0
+            };
+            assert_eq!(expected, row, "Unexpected pixels in row {}", y);
+        }
+    }
+
+    #[test]
+    fn dxy0_draws_nothing_in_lowres_mode() {
+        let mut instructions = vec![0xA206, 0xD010, 0x1204];
+        instructions.extend([0xFFFF; 16]);
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        assert!(!cpu.display.is_hires());
+        assert!(cpu.display.pixels.iter().all(|&row| row == 0));
+    }
+
+    #[test]
+    fn dxy0_draws_16x16_sprite_in_superchip_lowres_mode() {
+        let mut instructions = vec![0xA206, 0xD010, 0x1204];
+        instructions.extend([0xFFFF; 16]);
+        let rom = Rom::from_raw_instructions(&instructions);
+        let config = CpuConfig::for_mode(CompatibilityMode::SuperChip);
+        let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        assert!(!cpu.display.is_hires());
+        for (y, &row) in cpu.display.pixels.iter().enumerate() {
+            let expected = if y < 16 { 0xFFFF << (64 - 16) } else { 0 };
+            assert_eq!(expected, row, "Unexpected pixels in row {}", y);
+        }
+    }
+
+    #[test]
+    fn dxyn_sets_vf_on_collision() {
+        let instructions = vec![0x6F01, 0xA20A, 0xD011, 0xD011, 0x1208, 0xFF00];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(0, cpu.registers.get_value(U4::new(0xF)));
+
+        cpu.tick().unwrap();
+        assert_eq!(1, cpu.registers.get_value(U4::new(0xF)));
+    }
+
+    #[test]
+    fn dxy0_sets_vf_on_collision() {
+        let mut instructions = vec![0x00FF, 0xA20C, 0xD010, 0xD010, 0x8000, 0x1208];
+        instructions.extend([0xFFFF; 16]);
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(0, cpu.registers.get_value(U4::new(0xF)));
+
+        cpu.tick().unwrap();
+        assert_eq!(1, cpu.registers.get_value(U4::new(0xF)));
+    }
+
+    #[test]
+    fn resolution_switch_instructions() {
+        let rom = Rom::from_raw_instructions(&[0x00FF, 0x00FE]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        cpu.tick().unwrap();
+        assert!(cpu.display.is_hires());
+
+        cpu.tick().unwrap();
+        assert!(!cpu.display.is_hires());
+    }
+
+    #[test]
+    fn config_controls_vf_reset_on_logic() {
+        for reset_vf_on_logic in [true, false] {
+            let instructions = vec![0x6F05, 0x8011];
+            let rom = Rom::from_raw_instructions(&instructions);
+            let config = CpuConfig {
+                reset_vf_on_logic,
+                ..Default::default()
+            };
+            let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+
+            cpu.tick().unwrap();
+            cpu.tick().unwrap();
+
+            let expected = if reset_vf_on_logic { 0 } else { 5 };
+            assert_eq!(expected, cpu.registers.get_value(U4::new(0xF)));
+        }
+    }
+
+    #[test]
+    fn lenient_config_skips_invalid_instructions() {
+        let instructions = vec![0x0000, 0x6001];
+        let config = CpuConfig {
+            lenient: true,
+            ..Default::default()
+        };
+        let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(
+            Rom::from_raw_instructions(&instructions),
+            config,
+        )
+        .unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        assert_eq!(0x204, cpu.pc());
+        assert_eq!(1, cpu.get_register(U4::new(0)));
+
+        let mut strict_cpu =
+            Cpu::<MockKeypad>::from_rom(Rom::from_raw_instructions(&instructions)).unwrap();
+        assert!(strict_cpu.tick().is_err());
+    }
+
+    #[test]
+    fn builder_applies_every_part() {
+        let instructions = vec![0xE09E, 0x0000, 0x2206, 0x2206];
+        let config = CpuConfig {
+            wrap_sprites: true,
+            ..CpuConfig::for_mode(CompatibilityMode::SuperChip)
+        };
+        let mut cpu = CpuBuilder::new()
+            .with_rom(Rom::from_raw_instructions(&instructions))
+            .with_keypad(MockKeypad::from_value(0))
+            .with_config(config)
+            .with_seed(42)
+            .with_stack_limit(1)
+            .build()
+            .unwrap();
+
+        assert_eq!(42, cpu.seed());
+        assert!(cpu.rom_checksum().is_some());
+        assert_eq!(
+            CompatibilityMode::SuperChip,
+            cpu.config().compatibility_mode
+        );
+        assert!(cpu.display.wrap_sprites);
+
+        // the pressed key skips the invalid instruction
+        cpu.tick().unwrap();
+        assert_eq!(0x204, cpu.pc());
+
+        cpu.tick().unwrap();
+        assert!(cpu.tick().is_err());
+    }
+
+    #[test]
+    fn builder_without_parts_matches_default_cpu() {
+        let cpu = CpuBuilder::<MockKeypad>::new().build().unwrap();
+
+        assert_eq!(CpuConfig::default(), *cpu.config());
+        assert!(cpu.rom_checksum().is_none());
+        assert_eq!(0x200, cpu.pc());
+    }
+
+    #[test]
+    fn config_limits_stack_depth() {
+        let rom = Rom::from_raw_instructions(&[0x2200]);
+        let config = CpuConfig {
+            max_stack_depth: 2,
+            ..Default::default()
+        };
+        let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        assert!(cpu.tick().is_err());
+        assert_eq!(2, cpu.stack_depth());
+    }
+
+    #[test]
+    fn config_survives_reset() {
+        let config = CpuConfig {
+            cpu_rate_hz: 1200,
+            wrap_sprites: true,
+            ..CpuConfig::for_mode(CompatibilityMode::Chip48)
+        };
+        let mut cpu =
+            Cpu::<MockKeypad>::from_rom_with_config(Rom::from_raw_instructions(&[0x00E0]), config)
+                .unwrap();
+        assert!(cpu.display.wrap_sprites);
+
+        cpu.reset();
+
+        assert_eq!(config, *cpu.config());
+        assert!(cpu.display.wrap_sprites);
+        assert!(!cpu.config().reset_vf_on_logic);
+    }
+
+    #[test]
+    fn fx01_selects_planes_for_drawing() {
+        let instructions = vec![
+            0xF301, // select both planes
+            0xA20A, // point I at the sprite data
+            0xD011, // draw one row per plane at 0, 0
+            0x1206, // loop
+            0x0000, // padding
+            0x80C0, // sprite rows of the first and the second plane
+        ];
+
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        for _ in 0..3 {
+            cpu.tick().unwrap();
+        }
+
+        assert_eq!(0b11, cpu.display.current_plane());
+        assert_eq!(3, cpu.display.pixel_color(0, 0));
+        assert_eq!(2, cpu.display.pixel_color(1, 0));
+    }
+
+    #[test]
+    fn scroll_instructions_move_display() {
+        let instructions = vec![0x6000, 0xA000, 0xD001, 0x00C3, 0x00FB, 0x00FC, 0x00FC];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        for _ in 0..4 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(0xF0 << 56, cpu.display.pixels[3]);
+
+        cpu.tick().unwrap();
+        assert_eq!(0xF0 << 52, cpu.display.pixels[3]);
+
+        cpu.tick().unwrap();
+        assert_eq!(0xF0 << 56, cpu.display.pixels[3]);
+
+        cpu.tick().unwrap();
+        assert!(cpu.display.pixels.iter().all(|&row| row == 0));
+    }
+
+    #[test]
+    fn load_state_rejects_state_of_other_rom() {
+        let rom = Rom::from_raw_instructions(&[0x6A12, 0x1200]);
+        let other_rom = Rom::from_raw_instructions(&[0x6A13, 0x1200]);
+        let cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        let mut other_cpu = Cpu::<MockKeypad>::from_rom(other_rom).unwrap();
+
+        assert!(other_cpu.load_state(cpu.save_state()).is_err());
+        assert_eq!(0x6A13, other_cpu.memory().read_instruction(MEMORY_START));
+
+        let mut restarted_cpu =
+            Cpu::<MockKeypad>::from_rom(Rom::from_raw_instructions(&[0x6A12, 0x1200])).unwrap();
+        assert!(restarted_cpu.load_state(cpu.save_state()).is_ok());
+    }
+
+    #[test]
+    fn tick_count_counts_executed_ticks() {
+        let rom = Rom::from_file("./roms/ibm-logo.ch8").unwrap();
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        let mut ticks = 0;
+        while cpu.pc() != 0x228 {
+            cpu.tick().unwrap();
+            ticks += 1;
+        }
+
+        assert_eq!(20, cpu.tick_count());
+        assert_eq!(ticks, cpu.tick_count());
+
+        cpu.reset();
+        assert_eq!(0, cpu.tick_count());
+    }
+
+    #[test]
+    fn registers_are_displayed_on_one_line() {
+        let mut registers = VariableRegisters::new();
+        registers.set_value(U4::new(0x1), 0xFF);
+        registers.set_value(U4::new(0x2), 0xA3);
+        registers.set_value(U4::new(0xF), 0x01);
+
+        assert_eq!(
+            "V0:00 V1:FF V2:A3 V3:00 V4:00 V5:00 V6:00 V7:00 \
+             V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:01",
+            registers.to_string()
+        );
+    }
+
+    #[test]
+    fn flag_is_stored_in_vf() {
+        let mut registers = VariableRegisters::new();
+        assert!(!registers.get_flag());
+
+        registers.set_flag(true);
+        assert_eq!(1, registers.get_value(U4::new(0xF)));
+        assert!(registers.get_flag());
+
+        registers.set_value(U4::new(0xF), 0x80);
+        assert!(registers.get_flag());
+        registers.set_flag(false);
+        assert_eq!(0, registers.get_value(U4::new(0xF)));
+    }
+
+    #[test]
+    fn register_iterator_yields_all_registers_in_order() {
+        let mut registers = VariableRegisters::new();
+        registers.set_value(U4::new(0xA), 0x42);
+
+        let values = registers
+            .iter()
+            .map(|(register, value)| (*register, value))
+            .collect::<Vec<_>>();
+
+        assert_eq!(16, values.len());
+        assert!(values
+            .iter()
+            .enumerate()
+            .all(|(idx, (register, _))| idx as u8 == *register));
+        assert_eq!((0xA, 0x42), values[0xA]);
+        assert_eq!(1, values.iter().filter(|(_, value)| *value != 0).count());
+    }
+
+    #[test]
+    fn test_roms_run_until_halt() {
+        let roms = [
+            (
+                "ibm-logo",
+                Rom::from_file("./roms/ibm-logo.ch8").unwrap(),
+                CompatibilityMode::Chip8,
+                0x228,
+            ),
+            (
+                "bc_test",
+                Rom::from_file("./roms/bc_test.ch8").unwrap(),
+                CompatibilityMode::SuperChip,
+                0x30E,
+            ),
+            (
+                "subroutine loop",
+                Rom::from_raw_instructions(&[0x2206, 0x1202, 0x0000, 0x7001, 0x00EE]),
+                CompatibilityMode::SuperChip,
+                0x202,
+            ),
+        ];
+
+        for (name, rom, mode, halt_address) in roms {
+            let config = CpuConfig::for_mode(mode);
+            let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+
+            let reason = cpu.run_until_halt(100_000);
+
+            assert!(
+                matches!(reason, HaltReason::InfiniteLoop { address } if *address == halt_address),
+                "{}: {:?}",
+                name,
+                reason
+            );
+        }
+    }
+
+    #[test]
+    fn run_for_cycles_stops_at_first_error_unless_lenient() {
+        let instructions = vec![0x7001, 0x0000, 0x7001, 0x1200];
+
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        let (completed, error) = cpu.run_for_cycles(10);
+        assert_eq!(1, completed);
+        assert!(error.is_some());
+
+        let config = CpuConfig {
+            lenient: true,
+            ..Default::default()
+        };
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+        let (completed, error) = cpu.run_for_cycles(10);
+        assert_eq!(10, completed);
+        assert!(error.is_none());
+        assert_eq!(5, cpu.get_register(U4::new(0)));
+    }
+
+    #[test]
+    fn lenient_cpu_fails_at_the_end_of_empty_memory() {
+        let config = CpuConfig {
+            lenient: true,
+            ..Default::default()
+        };
+        let rom = Rom::from_raw_instructions(&[]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, config).unwrap();
+
+        let (completed, error) = cpu.run_for_cycles(4096);
+
+        assert_eq!(((crate::memory::MEMORY_SIZE - 0x200) / 2) as u64, completed);
+        assert!(error.is_some());
+    }
+
+    #[test]
+    fn exit_instruction_stops_further_ticks() {
+        let rom = Rom::from_raw_instructions(&[0x6001, 0x00FD, 0x6002]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert!(cpu.has_exited());
+
+        cpu.tick().unwrap();
+        assert_eq!(1, cpu.get_register(U4::new(0)));
+        assert_eq!(0x204, cpu.pc());
+        assert_eq!(2, cpu.tick_count());
+
+        let instructions = [0x6001, 0x00FD, 0x6002];
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        assert!(matches!(cpu.run_for_cycles(10), (2, None)));
+
+        let rom = Rom::from_raw_instructions(&instructions);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        assert!(matches!(cpu.run_until_halt(10), HaltReason::Exited));
+    }
+
+    #[test]
+    fn run_until_halt_stops_at_cycle_limit_and_errors() {
+        let rom = Rom::from_raw_instructions(&[0x7001, 0x1200]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        assert!(matches!(cpu.run_until_halt(10), HaltReason::CycleLimit(10)));
+        assert_eq!(10, cpu.tick_count());
+
+        let rom = Rom::from_raw_instructions(&[0x6001, 0x00EE]);
+        let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+        assert!(matches!(cpu.run_until_halt(10), HaltReason::Error(_)));
+    }
 }
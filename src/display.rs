@@ -1,51 +1,715 @@
-use crate::U4;
+use anyhow::{anyhow, Result};
+
+use crate::bits::join_bytes;
 
 const DISPLAY_WIDTH: usize = 64;
 const DISPLAY_HEIGHT: usize = 32;
+const HIRES_DISPLAY_WIDTH: usize = 128;
+const HIRES_DISPLAY_HEIGHT: usize = 64;
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+const SPRITE_WIDTH: usize = 8;
+const EXTENDED_SPRITE_WIDTH: usize = 16;
 
+/// Bit mask of the first plane, the only plane selected by default
+const FIRST_PLANE: u8 = 0b01;
+const PLANE_COUNT: usize = 2;
+
+#[derive(Clone, PartialEq)]
 pub struct Display {
     pub pixels: [u64; DISPLAY_HEIGHT],
+    /// Second XO-CHIP color plane
+    pub plane2: [u64; DISPLAY_HEIGHT],
+    /// Pixels of the SUPER-CHIP 128x64 high resolution mode
+    pub hires_pixels: [u128; HIRES_DISPLAY_HEIGHT],
+    /// Second XO-CHIP color plane of the high resolution mode
+    pub hires_plane2: [u128; HIRES_DISPLAY_HEIGHT],
+    hires: bool,
+    /// Planes affected by drawing, clearing and scrolling: 0b01 for the first, 0b10 for the second
+    /// and 0b11 for both planes
+    current_plane: u8,
+    /// Sprites crossing the display edges wrap around instead of being clipped
+    pub wrap_sprites: bool,
 }
 
 impl Display {
     pub fn new() -> Self {
         Self {
             pixels: [0; DISPLAY_HEIGHT],
+            plane2: [0; DISPLAY_HEIGHT],
+            hires_pixels: [0; HIRES_DISPLAY_HEIGHT],
+            hires_plane2: [0; HIRES_DISPLAY_HEIGHT],
+            hires: false,
+            current_plane: FIRST_PLANE,
+            wrap_sprites: false,
         }
     }
 
+    /// Clears the selected planes
     pub fn clear(&mut self) {
-        self.pixels = [0; DISPLAY_HEIGHT];
+        if self.current_plane & 0b01 > 0 {
+            self.pixels = [0; DISPLAY_HEIGHT];
+            self.hires_pixels = [0; HIRES_DISPLAY_HEIGHT];
+        }
+        if self.current_plane & 0b10 > 0 {
+            self.plane2 = [0; DISPLAY_HEIGHT];
+            self.hires_plane2 = [0; HIRES_DISPLAY_HEIGHT];
+        }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switches between the 64x32 and the 128x64 resolution. Both planes are cleared on every switch.
+    pub fn set_hires(&mut self, hires: bool) {
+        *self = Self {
+            hires,
+            current_plane: self.current_plane,
+            wrap_sprites: self.wrap_sprites,
+            ..Self::new()
+        };
+    }
+
+    pub fn current_plane(&self) -> u8 {
+        self.current_plane
+    }
+
+    /// Selects the planes by their bit mask, only the lowest two bits are used
+    pub fn set_plane(&mut self, planes: u8) {
+        self.current_plane = planes & 0b11;
+    }
+
+    pub fn selected_plane_count(&self) -> usize {
+        self.selected_planes().count()
+    }
+
+    fn selected_planes(&self) -> impl Iterator<Item = usize> {
+        let current_plane = self.current_plane;
+        (0..PLANE_COUNT).filter(move |plane| current_plane & (1 << plane) > 0)
+    }
+
+    /// Returns the width and height of the active resolution
+    pub fn resolution(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT)
+        } else {
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        }
+    }
+
+    /// Returns a row of the first plane in the active resolution, the leftmost pixel being the highest used bit
+    pub fn row(&self, y_pos: usize) -> u128 {
+        self.plane_row(0, y_pos)
     }
 
+    /// Returns a row of the given plane, 0 being the first plane
+    pub fn plane_row(&self, plane: usize, y_pos: usize) -> u128 {
+        match (self.hires, plane) {
+            (false, 0) => self.pixels[y_pos] as u128,
+            (false, _) => self.plane2[y_pos] as u128,
+            (true, 0) => self.hires_pixels[y_pos],
+            (true, _) => self.hires_plane2[y_pos],
+        }
+    }
+
+    fn set_plane_row(&mut self, plane: usize, y_pos: usize, row: u128) {
+        match (self.hires, plane) {
+            (false, 0) => self.pixels[y_pos] = row as u64,
+            (false, _) => self.plane2[y_pos] = row as u64,
+            (true, 0) => self.hires_pixels[y_pos] = row,
+            (true, _) => self.hires_plane2[y_pos] = row,
+        }
+    }
+
+    /// Counts the pixels set in any plane of the active resolution
+    pub fn count_lit_pixels(&self) -> u32 {
+        let (_, height) = self.resolution();
+        (0..height)
+            .map(|y| (self.plane_row(0, y) | self.plane_row(1, y)).count_ones())
+            .sum()
+    }
+
+    /// Combines both planes into a color index: 0 is the background, 1 the first plane,
+    /// 2 the second plane and 3 both planes
+    pub fn pixel_color(&self, x_pos: usize, y_pos: usize) -> u8 {
+        let (width, _) = self.resolution();
+        let pixel_mask = 1 << (width - 1 - x_pos);
+        (0..PLANE_COUNT)
+            .filter(|&plane| self.plane_row(plane, y_pos) & pixel_mask > 0)
+            .map(|plane| 1 << plane)
+            .sum()
+    }
+
+    /// Moves all rows of the selected planes down, the top rows are cleared
+    pub fn scroll_down(&mut self, n: usize) {
+        let (_, height) = self.resolution();
+        for plane in self.selected_planes().collect::<Vec<_>>() {
+            for y_pos in (0..height).rev() {
+                let row = if y_pos >= n {
+                    self.plane_row(plane, y_pos - n)
+                } else {
+                    0
+                };
+                self.set_plane_row(plane, y_pos, row);
+            }
+        }
+    }
+
+    /// Moves all pixels of the selected planes to the right, the leftmost columns are cleared
+    pub fn scroll_right(&mut self, n: usize) {
+        let (width, height) = self.resolution();
+        for plane in self.selected_planes().collect::<Vec<_>>() {
+            for y_pos in 0..height {
+                let row = if n < width {
+                    self.plane_row(plane, y_pos) >> n
+                } else {
+                    0
+                };
+                self.set_plane_row(plane, y_pos, row);
+            }
+        }
+    }
+
+    /// Moves all pixels of the selected planes to the left, the rightmost columns are cleared
+    pub fn scroll_left(&mut self, n: usize) {
+        let (width, height) = self.resolution();
+        let row_mask = u128::MAX >> (HIRES_DISPLAY_WIDTH - width);
+        for plane in self.selected_planes().collect::<Vec<_>>() {
+            for y_pos in 0..height {
+                let row = if n < width {
+                    (self.plane_row(plane, y_pos) << n) & row_mask
+                } else {
+                    0
+                };
+                self.set_plane_row(plane, y_pos, row);
+            }
+        }
+    }
+
+    /// Draws the sprite to the selected planes. With several selected planes the sprite contains
+    /// the rows of each plane one after another.
     pub fn draw(&mut self, x_pos: u8, y_pos: u8, sprite: &[u8]) -> bool {
-        let x_pos = x_pos as usize % DISPLAY_WIDTH;
-        let y_pos = y_pos as usize % DISPLAY_HEIGHT;
+        let rows = sprite.iter().map(|&row| row as u128).collect::<Vec<_>>();
+        self.draw_planes(x_pos, y_pos, &rows, SPRITE_WIDTH)
+    }
 
+    /// Draws a 16 pixel wide SUPER-CHIP sprite, every row is made of two bytes
+    pub fn draw_extended(&mut self, x_pos: u8, y_pos: u8, sprite: &[u8]) -> bool {
+        let rows = sprite
+            .chunks(2)
+            .map(|bytes| join_bytes(bytes[0], bytes.get(1).copied().unwrap_or(0)) as u128)
+            .collect::<Vec<_>>();
+        self.draw_planes(x_pos, y_pos, &rows, EXTENDED_SPRITE_WIDTH)
+    }
+
+    fn draw_planes(&mut self, x_pos: u8, y_pos: u8, rows: &[u128], sprite_width: usize) -> bool {
+        let planes = self.selected_planes().collect::<Vec<_>>();
+        if planes.is_empty() || rows.is_empty() {
+            return false;
+        }
+
+        let rows_per_plane = (rows.len() / planes.len()).max(1);
         let mut has_turned_of_any_pixel = false;
-        for (row_idx, &sprite_row) in sprite.into_iter().enumerate() {
-            let current_y = y_pos + row_idx;
-            if current_y >= DISPLAY_HEIGHT {
-                break;
-            }
+        for (plane, plane_rows) in planes.into_iter().zip(rows.chunks(rows_per_plane)) {
+            has_turned_of_any_pixel |=
+                self.draw_rows(plane, x_pos, y_pos, plane_rows, sprite_width);
+        }
 
-            let shifted_sprite_row = self.shift_sprite_row(x_pos as u64, sprite_row as u64);
-            if (shifted_sprite_row & self.pixels[current_y]) > 0 {
-                has_turned_of_any_pixel = true;
+        has_turned_of_any_pixel
+    }
+
+    fn draw_rows(
+        &mut self,
+        plane: usize,
+        x_pos: u8,
+        y_pos: u8,
+        rows: &[u128],
+        sprite_width: usize,
+    ) -> bool {
+        let (width, height) = self.resolution();
+        let x_pos = x_pos as usize % width;
+        let y_pos = y_pos as usize % height;
+
+        let mut has_turned_of_any_pixel = false;
+        for (row_idx, &sprite_row) in rows.iter().enumerate() {
+            let mut current_y = y_pos + row_idx;
+            if current_y >= height {
+                if !self.wrap_sprites {
+                    break;
+                }
+                current_y %= height;
             }
 
-            self.pixels[current_y] = self.pixels[current_y] ^ shifted_sprite_row;
+            has_turned_of_any_pixel |=
+                self.xor_row_at(plane, current_y, x_pos, sprite_row, sprite_width);
         }
 
         has_turned_of_any_pixel
     }
 
-    fn shift_sprite_row(&self, x_pos: u64, sprite_row: u64) -> u64 {
-        let pos = 64 - 8;
+    /// Draws a single 8 pixel sprite row to the selected planes, the position wraps around the
+    /// display. Returns true if any set pixel was cleared.
+    pub fn xor_sprite_row_at(&mut self, y_pos: usize, x_pos: usize, sprite_byte: u8) -> bool {
+        let planes = self.selected_planes().collect::<Vec<_>>();
+        let mut has_turned_of_any_pixel = false;
+        for plane in planes {
+            has_turned_of_any_pixel |=
+                self.xor_row_at(plane, y_pos, x_pos, sprite_byte as u128, SPRITE_WIDTH);
+        }
+
+        has_turned_of_any_pixel
+    }
+
+    fn xor_row_at(
+        &mut self,
+        plane: usize,
+        y_pos: usize,
+        x_pos: usize,
+        sprite_row: u128,
+        sprite_width: usize,
+    ) -> bool {
+        let (width, height) = self.resolution();
+        let y_pos = y_pos % height;
+        let shifted_sprite_row = self.shift_sprite_row(x_pos % width, sprite_row, sprite_width);
+        let row = self.plane_row(plane, y_pos);
+        self.set_plane_row(plane, y_pos, row ^ shifted_sprite_row);
+
+        (shifted_sprite_row & row) > 0
+    }
+
+    /// Parses a display from text art in the first plane, the inverse of `as_text_art`. 64 lines of
+    /// 128 characters result in the high resolution mode, otherwise 32 lines of 64 characters are expected.
+    pub fn from_text_art(text: &str, on: char, off: char) -> Result<Self> {
+        let lines = text.lines().collect::<Vec<_>>();
+        let mut display = Self::new();
+        display.set_hires(lines.len() == HIRES_DISPLAY_HEIGHT);
+
+        let (width, height) = display.resolution();
+        if lines.len() != height {
+            return Err(anyhow!(
+                "Expected {} lines, got {}",
+                DISPLAY_HEIGHT,
+                lines.len()
+            ));
+        }
+
+        for (y_pos, line) in lines.into_iter().enumerate() {
+            let pixels = line.chars().collect::<Vec<_>>();
+            if pixels.len() != width {
+                return Err(anyhow!(
+                    "Expected {} characters in line {}, got {}",
+                    width,
+                    y_pos + 1,
+                    pixels.len()
+                ));
+            }
+
+            let mut row = 0;
+            for (x_pos, pixel) in pixels.into_iter().enumerate() {
+                let bit = match pixel {
+                    c if c == on => 1,
+                    c if c == off => 0,
+                    c => {
+                        return Err(anyhow!(
+                            "Unexpected character '{}' at column {} of line {}",
+                            c,
+                            x_pos + 1,
+                            y_pos + 1
+                        ))
+                    }
+                };
+                row = (row << 1) | bit;
+            }
+            display.set_plane_row(0, y_pos, row);
+        }
+
+        Ok(display)
+    }
+
+    /// Renders the active resolution line by line, using `on` for pixels set in any plane and `off` for clear pixels
+    pub fn as_text_art(&self, on: char, off: char) -> String {
+        let (width, height) = self.resolution();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| if self.pixel_color(x, y) > 0 { on } else { off })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// FNV-1a hash over the pixels of both planes in the active resolution, for comparing frames without rendering
+    pub fn hash(&self) -> u64 {
+        let (width, height) = self.resolution();
+        let row_bytes = width / 8;
+        (0..PLANE_COUNT)
+            .flat_map(|plane| (0..height).map(move |y| (plane, y)))
+            .flat_map(|(plane, y)| {
+                self.plane_row(plane, y)
+                    .to_be_bytes()
+                    .into_iter()
+                    .skip(16 - row_bytes)
+            })
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+            })
+    }
+
+    fn shift_sprite_row(&self, x_pos: usize, sprite_row: u128, sprite_width: usize) -> u128 {
+        let (width, _) = self.resolution();
+        let pos = width - sprite_width;
         if x_pos <= pos {
             return sprite_row << (pos - x_pos);
         }
 
-        return sprite_row >> (x_pos - pos);
+        let overflow = x_pos - pos;
+        let shifted_sprite_row = sprite_row >> overflow;
+        if !self.wrap_sprites {
+            return shifted_sprite_row;
+        }
+
+        let row_mask = u128::MAX >> (HIRES_DISPLAY_WIDTH - width);
+        shifted_sprite_row | ((sprite_row << (width - overflow)) & row_mask)
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_clips_sprite_at_bottom_edge() {
+        let mut display = Display::new();
+
+        let has_turned_off_pixel = display.draw(0, 31, &[0xFF, 0xFF]);
+
+        assert!(!has_turned_off_pixel);
+        assert_eq!(0xFF << 56, display.pixels[31]);
+        assert!(display.pixels[..31].iter().all(|&row| row == 0));
+    }
+
+    #[test]
+    fn count_lit_pixels_reflects_drawn_and_erased_pixels() {
+        let mut display = Display::new();
+        assert_eq!(0, display.count_lit_pixels());
+
+        // font glyph of 0
+        display.draw(0, 0, &[0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        assert_eq!(14, display.count_lit_pixels());
+
+        let has_turned_off_pixel = display.draw(0, 0, &[0xF0]);
+        assert!(has_turned_off_pixel);
+        assert_eq!(10, display.count_lit_pixels());
+
+        display.clear();
+        assert_eq!(0, display.count_lit_pixels());
+    }
+
+    #[test]
+    fn draw_clips_sprite_at_right_edge() {
+        let mut display = Display::new();
+
+        display.draw(60, 0, &[0xFF]);
+
+        assert_eq!(0xF, display.pixels[0]);
+    }
+
+    #[test]
+    fn draw_wraps_sprite_at_right_edge() {
+        let mut display = Display::new();
+        display.wrap_sprites = true;
+
+        display.draw(60, 0, &[0xFF]);
+
+        assert_eq!(0xF000_0000_0000_000F, display.pixels[0]);
+    }
+
+    #[test]
+    fn draw_wraps_sprite_at_bottom_edge() {
+        let mut display = Display::new();
+        display.wrap_sprites = true;
+
+        display.draw(0, 31, &[0xFF, 0x81]);
+
+        assert_eq!(0xFF << 56, display.pixels[31]);
+        assert_eq!(0x81 << 56, display.pixels[0]);
+    }
+
+    #[test]
+    fn xor_sprite_row_at_shifts_and_toggles_a_single_row() {
+        let mut display = Display::new();
+
+        assert!(!display.xor_sprite_row_at(3, 0, 0b1100_0011));
+        assert_eq!(0b1100_0011 << 56, display.pixels[3]);
+
+        assert!(!display.xor_sprite_row_at(3, 10, 0xFF));
+        assert_eq!((0b1100_0011 << 56) | (0xFF << 46), display.pixels[3]);
+
+        assert!(display.xor_sprite_row_at(3, 4, 0xFF));
+        assert_eq!(
+            ((0b1100_0011 << 56) | (0xFF << 46)) ^ (0xFF << 52),
+            display.pixels[3]
+        );
+        assert!(display
+            .pixels
+            .iter()
+            .enumerate()
+            .all(|(y, &row)| y == 3 || row == 0));
+    }
+
+    #[test]
+    fn xor_sprite_row_at_clips_at_the_right_edge() {
+        let mut display = Display::new();
+
+        assert!(!display.xor_sprite_row_at(0, 60, 0xFF));
+        assert_eq!(0xF, display.pixels[0]);
+
+        display.wrap_sprites = true;
+        assert!(!display.xor_sprite_row_at(33, 124, 0xFF));
+        assert_eq!(0xF000_0000_0000_000F, display.pixels[1]);
+        assert!(display.xor_sprite_row_at(1, 60, 0xFF));
+        assert_eq!(0, display.pixels[1]);
+    }
+
+    #[test]
+    fn text_art_round_trips() {
+        let mut display = Display::new();
+        display.draw(0, 0, &[0xF0, 0x0F]);
+        display.draw(60, 31, &[0xFF]);
+        let text = display.as_text_art('█', ' ');
+
+        let parsed = Display::from_text_art(&text, '█', ' ').unwrap();
+
+        assert!(parsed == display);
+        assert_eq!(text, parsed.as_text_art('█', ' '));
+
+        display.set_hires(true);
+        display.draw(120, 63, &[0xFF]);
+        let parsed = Display::from_text_art(&display.as_text_art('#', '.'), '#', '.').unwrap();
+        assert!(parsed.is_hires());
+        assert!(parsed == display);
+    }
+
+    #[test]
+    fn invalid_text_art_is_rejected() {
+        let row = ".".repeat(64);
+        let valid = vec![row.as_str(); 32].join("\n");
+        assert!(Display::from_text_art(&valid, '#', '.').is_ok());
+
+        let too_few_lines = vec![row.as_str(); 31].join("\n");
+        let err = Display::from_text_art(&too_few_lines, '#', '.')
+            .err()
+            .unwrap();
+        assert_eq!("Expected 32 lines, got 31", err.to_string());
+
+        let short_row = ".".repeat(63);
+        let mut lines = vec![row.as_str(); 32];
+        lines[4] = &short_row;
+        let err = Display::from_text_art(&lines.join("\n"), '#', '.')
+            .err()
+            .unwrap();
+        assert_eq!("Expected 64 characters in line 5, got 63", err.to_string());
+
+        let err = Display::from_text_art(&valid.replacen('.', "x", 1), '#', '.')
+            .err()
+            .unwrap();
+        assert_eq!(
+            "Unexpected character 'x' at column 1 of line 1",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn empty_display_as_text_art() {
+        let display = Display::new();
+
+        let text = display.as_text_art('#', '.');
+
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(DISPLAY_HEIGHT, lines.len());
+        assert!(lines.iter().all(|line| *line == ".".repeat(DISPLAY_WIDTH)));
+    }
+
+    #[test]
+    fn sprite_as_text_art() {
+        let mut display = Display::new();
+        display.draw(2, 1, &[0b1010_0000, 0b0101_0000]);
+
+        let text = display.as_text_art('#', '.');
+
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(format!("..#.#{}", ".".repeat(59)), lines[1]);
+        assert_eq!(format!("...#.#{}", ".".repeat(58)), lines[2]);
+        assert_eq!(".".repeat(DISPLAY_WIDTH), lines[0]);
+    }
+
+    #[test]
+    fn draw_uses_hires_pixels_in_hires_mode() {
+        let mut display = Display::new();
+        display.set_hires(true);
+
+        display.draw(120, 63, &[0xFF]);
+
+        assert_eq!(0xFF, display.hires_pixels[63]);
+        assert!(display.pixels.iter().all(|&row| row == 0));
+    }
+
+    #[test]
+    fn draw_extended_draws_16_pixel_wide_rows() {
+        let mut display = Display::new();
+        display.set_hires(true);
+
+        let sprite = [0xFFu8; 32];
+        let has_turned_off_pixel = display.draw_extended(8, 2, &sprite);
+
+        assert!(!has_turned_off_pixel);
+        assert!(display.hires_pixels[..2].iter().all(|&row| row == 0));
+        assert!(display.hires_pixels[2..18]
+            .iter()
+            .all(|&row| row == 0xFFFF << (128 - 16 - 8)));
+        assert!(display.hires_pixels[18..].iter().all(|&row| row == 0));
+
+        assert!(display.draw_extended(8, 2, &sprite));
+        assert!(display.hires_pixels.iter().all(|&row| row == 0));
+    }
+
+    #[test]
+    fn draw_extended_wraps_at_right_edge_in_hires_mode() {
+        let mut display = Display::new();
+        display.set_hires(true);
+        display.wrap_sprites = true;
+
+        display.draw_extended(124, 0, &[0xFF, 0xFF]);
+
+        assert_eq!(0xF | (0xFFF << 116), display.hires_pixels[0]);
+    }
+
+    #[test]
+    fn switching_resolution_clears_display() {
+        let mut display = Display::new();
+        display.draw(0, 0, &[0xFF]);
+
+        display.set_hires(true);
+
+        assert_eq!((128, 64), display.resolution());
+        assert!(display.pixels.iter().all(|&row| row == 0));
+        assert_eq!(64, display.as_text_art('#', '.').lines().count());
+    }
+
+    #[test]
+    fn scroll_down_moves_rows_and_clears_top() {
+        let mut display = Display::new();
+        display.pixels[0] = 0b1010;
+        display.pixels[30] = 0xFF;
+
+        display.scroll_down(2);
+
+        assert_eq!([0, 0], display.pixels[..2]);
+        assert_eq!(0b1010, display.pixels[2]);
+        assert!(display.pixels[3..].iter().all(|&row| row == 0));
+    }
+
+    #[test]
+    fn scroll_right_moves_pixels_and_clears_left_columns() {
+        let mut display = Display::new();
+        display.pixels[5] = 1 << 63 | 0b1;
+
+        display.scroll_right(4);
+
+        assert_eq!(1 << 59, display.pixels[5]);
+    }
+
+    #[test]
+    fn scroll_left_moves_pixels_and_clears_right_columns() {
+        let mut display = Display::new();
+        display.pixels[5] = 1 << 63 | 0b1;
+
+        display.scroll_left(4);
+
+        assert_eq!(0b1_0000, display.pixels[5]);
+    }
+
+    #[test]
+    fn scroll_uses_hires_pixels_in_hires_mode() {
+        let mut display = Display::new();
+        display.set_hires(true);
+        display.hires_pixels[62] = 1 << 127 | 0b1;
+
+        display.scroll_down(1);
+        display.scroll_left(4);
+
+        assert_eq!(0b1_0000, display.hires_pixels[63]);
+        assert_eq!(0, display.hires_pixels[62]);
+
+        display.scroll_right(200);
+        assert!(display.hires_pixels.iter().all(|&row| row == 0));
+    }
+
+    #[test]
+    fn hash_changes_with_pixels() {
+        let mut display = Display::new();
+        let mut other_display = Display::new();
+        let empty_hash = display.hash();
+
+        display.draw(10, 4, &[0x81]);
+        other_display.draw(10, 4, &[0x81]);
+        assert_ne!(empty_hash, display.hash());
+        assert_eq!(display.hash(), other_display.hash());
+
+        other_display.draw(63, 31, &[0x80]);
+        assert_ne!(display.hash(), other_display.hash());
+    }
+
+    #[test]
+    fn draw_uses_selected_planes() {
+        let mut display = Display::new();
+
+        display.set_plane(0b10);
+        display.draw(0, 0, &[0b1100_0000]);
+        display.set_plane(0b11);
+        display.draw(0, 1, &[0b1000_0000, 0b0100_0000]);
+
+        assert_eq!(2, display.pixel_color(0, 0));
+        assert_eq!(2, display.pixel_color(1, 0));
+        assert_eq!(1, display.pixel_color(0, 1));
+        assert_eq!(2, display.pixel_color(1, 1));
+        assert_eq!(0, display.pixel_color(2, 0));
+
+        display.draw(0, 0, &[0b1000_0000, 0b1000_0000]);
+        assert_eq!(1, display.pixel_color(0, 0));
+        assert_eq!(1, display.pixel_color(0, 1));
+
+        display.draw(0, 5, &[0b1000_0000, 0b1000_0000]);
+        assert_eq!(3, display.pixel_color(0, 5));
+    }
+
+    #[test]
+    fn clear_and_scroll_only_affect_selected_planes() {
+        let mut display = Display::new();
+        display.set_plane(0b11);
+        display.draw(0, 0, &[0xFF, 0xFF]);
+
+        display.set_plane(0b01);
+        display.scroll_down(1);
+        assert_eq!(0xFF << 56, display.plane2[0]);
+        assert_eq!(0xFF << 56, display.pixels[1]);
+
+        display.clear();
+        assert!(display.pixels.iter().all(|&row| row == 0));
+        assert_eq!(0xFF << 56, display.plane2[0]);
+
+        display.set_plane(0);
+        assert!(!display.draw(0, 0, &[0xFF]));
+        assert_eq!(0, display.pixels[0]);
     }
 }
@@ -1,12 +1,28 @@
 use anyhow::{anyhow, Result};
+use smallvec::{smallvec, SmallVec};
 use std::fmt::Display;
 
 use crate::{
-    bits::{join_to_u16, join_to_u8, split_instruction, split_u16, split_u8, U4},
+    bits::{
+        join_bytes, join_nibbles, join_to_u16, join_to_u8, set_nibble_at, split_instruction,
+        split_u16, split_u8, U4,
+    },
     memory::MemoryAddress,
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InstructionCategory {
+    Arithmetic,
+    Bitwise,
+    Memory,
+    Display,
+    ControlFlow,
+    IO,
+    Timer,
+    Padding,
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum Instruction {
     AddValue {
         register: U4,
@@ -30,14 +46,41 @@ pub enum Instruction {
         register2: U4,
         sprite_length: U4,
     },
+    /// SUPER-CHIP 16x16 sprite, only drawn in the high resolution mode
+    DrawExtended {
+        register1: U4,
+        register2: U4,
+    },
+    EnableHighResolution,
+    DisableHighResolution,
+    /// SUPER-CHIP 00FD, stops the interpreter
+    ExitEmulator,
+    ScrollDown {
+        rows: U4,
+    },
+    ScrollRight,
+    ScrollLeft,
+    /// XO-CHIP Fx01, selects the color planes by a bit mask
+    SetPlane {
+        planes: U4,
+    },
     Jump(u16),
     JumpWithOffset(u16),
+    /// Undecodable word that is skipped in the lenient mode
+    Nop(u16),
     LoadDelayTimer {
         register: U4,
     },
     LoadFont {
         register: U4,
     },
+    LoadLargeFont {
+        register: U4,
+    },
+    /// XO-CHIP F000 NNNN, the address is stored in the word following the opcode
+    LoadLong {
+        address: u16,
+    },
     LoadRegisterFromKeyPress {
         register: U4,
     },
@@ -51,6 +94,9 @@ pub enum Instruction {
         register1: U4,
         register2: U4,
     },
+    LoadRplFlags {
+        register: U4,
+    },
     LoadSoundTimer {
         register: U4,
     },
@@ -63,6 +109,9 @@ pub enum Instruction {
         mask: u8,
     },
     Return,
+    SaveRplFlags {
+        register: U4,
+    },
     SetIndex(u16),
     SetValue {
         register: U4,
@@ -118,12 +167,26 @@ pub enum Instruction {
     },
 }
 
+/// Opcode of the 4 byte long XO-CHIP instruction that loads a 16 bit address into I
+pub const LOAD_LONG_OPCODE: u16 = 0xF000;
+
 impl Instruction {
+    /// Decodes an instruction from its high and low byte, as stored in memory
+    pub fn try_from_bytes(high: u8, low: u8) -> Result<Self> {
+        Self::try_from_u16(join_bytes(high, low))
+    }
+
     pub fn try_from_u16(raw_instruction: u16) -> Result<Self> {
         let (n1, n2, n3, n4) = split_instruction(raw_instruction);
         let res = match (*n1, *n2, *n3, *n4) {
             (0x0, 0x0, 0xE, 0x0) => Self::ClearScreen,
             (0x0, 0x0, 0xE, 0xE) => Self::Return,
+            (0x0, 0x0, 0xC, _) => Self::ScrollDown { rows: n4 },
+            (0x0, 0x0, 0xF, 0xB) => Self::ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => Self::ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Self::ExitEmulator,
+            (0x0, 0x0, 0xF, 0xE) => Self::DisableHighResolution,
+            (0x0, 0x0, 0xF, 0xF) => Self::EnableHighResolution,
             (0x0, _, _, _) => Err(anyhow!(
                 "Unsupported instruction 0x{:0>4X} System call",
                 raw_instruction
@@ -198,6 +261,10 @@ impl Instruction {
                 register: n2,
                 mask: join_to_u8(n3, n4),
             },
+            (0xD, _, _, 0x0) => Self::DrawExtended {
+                register1: n2,
+                register2: n3,
+            },
             (0xD, _, _, _) => Self::Draw {
                 register1: n2,
                 register2: n3,
@@ -205,26 +272,351 @@ impl Instruction {
             },
             (0xE, _, 0x9, 0xE) => Self::SkipIfKeyPressed { register: n2 },
             (0xE, _, 0xA, 0x1) => Self::SkipIfKeyNotPressed { register: n2 },
+            (0xF, _, 0x0, 0x1) => Self::SetPlane { planes: n2 },
             (0xF, _, 0x0, 0x7) => Self::LoadRegisterFromDelayTimer { register: n2 },
             (0xF, _, 0x0, 0xA) => Self::LoadRegisterFromKeyPress { register: n2 },
             (0xF, _, 0x1, 0x5) => Self::LoadDelayTimer { register: n2 },
             (0xF, _, 0x1, 0x8) => Self::LoadSoundTimer { register: n2 },
             (0xF, _, 0x1, 0xE) => Self::AddRegisterToIndex { register: n2 },
             (0xF, _, 0x2, 0x9) => Self::LoadFont { register: n2 },
+            (0xF, _, 0x3, 0x0) => Self::LoadLargeFont { register: n2 },
             (0xF, _, 0x3, 0x3) => Self::StoreBcdRepresentation { register: n2 },
             (0xF, _, 0x5, 0x5) => Self::WriteRegistersToMemory { register: n2 },
             (0xF, _, 0x6, 0x5) => Self::LoadRegistersFromMemory { register: n2 },
+            (0xF, _, 0x7, 0x5) => Self::SaveRplFlags { register: n2 },
+            (0xF, _, 0x8, 0x5) => Self::LoadRplFlags { register: n2 },
             (_, _, _, _) => Err(anyhow!("Invalid instruction 0x{:0>4X}", raw_instruction))?,
         };
 
         Ok(res)
     }
+
+    /// Decodes an instruction, treating undecodable words as padding
+    pub fn from_u16_lenient(raw_instruction: u16) -> Self {
+        Self::try_from_u16(raw_instruction).unwrap_or(Self::Nop(raw_instruction))
+    }
+
+    /// Encodes the instruction back into its raw opcode. The long index load only encodes its
+    /// F000 opcode, its address is stored in the following word.
+    pub fn to_u16(self) -> u16 {
+        match self {
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::DisableHighResolution => 0x00FE,
+            Instruction::ScrollDown { rows } => 0x00C0 | *rows as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::ExitEmulator => 0x00FD,
+            Instruction::SetPlane { planes } => encode_byte(0xF, planes, 0x01),
+            Instruction::EnableHighResolution => 0x00FF,
+            Instruction::Jump(address) => 0x1000 | address,
+            Instruction::Nop(raw_instruction) => raw_instruction,
+            Instruction::CallSubroutine(address) => 0x2000 | *address,
+            Instruction::SkipIfEqual { register, value } => encode_byte(0x3, register, value),
+            Instruction::SkipNotEqualByte { register, value } => encode_byte(0x4, register, value),
+            Instruction::SkipIfEqualRegisters {
+                register1,
+                register2,
+            } => encode_registers(0x5, register1, register2, 0x0),
+            Instruction::SetValue { register, value } => encode_byte(0x6, register, value),
+            Instruction::AddValue { register, value } => encode_byte(0x7, register, value),
+            Instruction::LoadRegisterFromRegister {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0x0),
+            Instruction::Or {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0x1),
+            Instruction::And {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0x2),
+            Instruction::Xor {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0x3),
+            Instruction::AddRegisters {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0x4),
+            Instruction::SubRegisters {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0x5),
+            Instruction::ShiftRight {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0x6),
+            Instruction::SubRegistersReversed {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0x7),
+            Instruction::ShiftLeft {
+                register1,
+                register2,
+            } => encode_registers(0x8, register1, register2, 0xE),
+            Instruction::SkipNotEqualRegisters {
+                register1,
+                register2,
+            } => encode_registers(0x9, register1, register2, 0x0),
+            Instruction::SetIndex(address) => 0xA000 | address,
+            Instruction::JumpWithOffset(address) => 0xB000 | address,
+            Instruction::Random { register, mask } => encode_byte(0xC, register, mask),
+            Instruction::Draw {
+                register1,
+                register2,
+                sprite_length,
+            } => join_nibbles(0xD, *register1, *register2, *sprite_length),
+            Instruction::DrawExtended {
+                register1,
+                register2,
+            } => encode_registers(0xD, register1, register2, 0x0),
+            Instruction::SkipIfKeyPressed { register } => encode_byte(0xE, register, 0x9E),
+            Instruction::SkipIfKeyNotPressed { register } => encode_byte(0xE, register, 0xA1),
+            Instruction::LoadRegisterFromDelayTimer { register } => {
+                encode_byte(0xF, register, 0x07)
+            }
+            Instruction::LoadRegisterFromKeyPress { register } => encode_byte(0xF, register, 0x0A),
+            Instruction::LoadDelayTimer { register } => encode_byte(0xF, register, 0x15),
+            Instruction::LoadSoundTimer { register } => encode_byte(0xF, register, 0x18),
+            Instruction::AddRegisterToIndex { register } => encode_byte(0xF, register, 0x1E),
+            Instruction::LoadFont { register } => encode_byte(0xF, register, 0x29),
+            Instruction::LoadLargeFont { register } => encode_byte(0xF, register, 0x30),
+            Instruction::LoadLong { .. } => LOAD_LONG_OPCODE,
+            Instruction::StoreBcdRepresentation { register } => encode_byte(0xF, register, 0x33),
+            Instruction::WriteRegistersToMemory { register } => encode_byte(0xF, register, 0x55),
+            Instruction::LoadRegistersFromMemory { register } => encode_byte(0xF, register, 0x65),
+            Instruction::SaveRplFlags { register } => encode_byte(0xF, register, 0x75),
+            Instruction::LoadRplFlags { register } => encode_byte(0xF, register, 0x85),
+        }
+    }
+
+    /// Registers the instruction writes to, including VF for instructions that set a flag.
+    /// The logic instructions include VF because they reset it in the CHIP-8 mode.
+    pub fn registers_written(&self) -> SmallVec<[U4; 2]> {
+        let flag = U4::new(0xF);
+        match *self {
+            Instruction::AddValue { register, .. }
+            | Instruction::LoadRegisterFromKeyPress { register }
+            | Instruction::LoadRegisterFromDelayTimer { register }
+            | Instruction::Random { register, .. }
+            | Instruction::SetValue { register, .. } => smallvec![register],
+            Instruction::LoadRegisterFromRegister { register1, .. } => smallvec![register1],
+            Instruction::AddRegisters { register1, .. }
+            | Instruction::And { register1, .. }
+            | Instruction::Or { register1, .. }
+            | Instruction::ShiftLeft { register1, .. }
+            | Instruction::ShiftRight { register1, .. }
+            | Instruction::SubRegisters { register1, .. }
+            | Instruction::SubRegistersReversed { register1, .. }
+            | Instruction::Xor { register1, .. } => smallvec![register1, flag],
+            Instruction::Draw { .. } | Instruction::DrawExtended { .. } => smallvec![flag],
+            Instruction::LoadRegistersFromMemory { register }
+            | Instruction::LoadRplFlags { register } => (0..=*register).map(U4::new).collect(),
+            Instruction::AddRegisterToIndex { .. }
+            | Instruction::CallSubroutine(_)
+            | Instruction::ClearScreen
+            | Instruction::EnableHighResolution
+            | Instruction::DisableHighResolution
+            | Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::ExitEmulator
+            | Instruction::SetPlane { .. }
+            | Instruction::Jump(_)
+            | Instruction::JumpWithOffset(_)
+            | Instruction::LoadDelayTimer { .. }
+            | Instruction::Nop(_)
+            | Instruction::LoadFont { .. }
+            | Instruction::LoadLargeFont { .. }
+            | Instruction::LoadLong { .. }
+            | Instruction::LoadSoundTimer { .. }
+            | Instruction::Return
+            | Instruction::SaveRplFlags { .. }
+            | Instruction::SetIndex(_)
+            | Instruction::StoreBcdRepresentation { .. }
+            | Instruction::SkipIfEqual { .. }
+            | Instruction::SkipIfEqualRegisters { .. }
+            | Instruction::SkipNotEqualByte { .. }
+            | Instruction::SkipIfKeyPressed { .. }
+            | Instruction::SkipIfKeyNotPressed { .. }
+            | Instruction::SkipNotEqualRegisters { .. }
+            | Instruction::WriteRegistersToMemory { .. } => SmallVec::new(),
+        }
+    }
+
+    /// Registers the instruction reads. Instructions with mode dependent sources include every
+    /// register they may read: the shifts read Vx and Vy, Bnnn reads V0 and the Vx of the SUPER-CHIP mode.
+    pub fn registers_read(&self) -> SmallVec<[U4; 3]> {
+        match *self {
+            Instruction::AddValue { register, .. }
+            | Instruction::AddRegisterToIndex { register }
+            | Instruction::LoadDelayTimer { register }
+            | Instruction::LoadFont { register }
+            | Instruction::LoadLargeFont { register }
+            | Instruction::LoadSoundTimer { register }
+            | Instruction::SkipIfEqual { register, .. }
+            | Instruction::SkipNotEqualByte { register, .. }
+            | Instruction::SkipIfKeyPressed { register }
+            | Instruction::SkipIfKeyNotPressed { register }
+            | Instruction::StoreBcdRepresentation { register } => smallvec![register],
+            Instruction::LoadRegisterFromRegister { register2, .. } => smallvec![register2],
+            Instruction::AddRegisters {
+                register1,
+                register2,
+            }
+            | Instruction::And {
+                register1,
+                register2,
+            }
+            | Instruction::Or {
+                register1,
+                register2,
+            }
+            | Instruction::Xor {
+                register1,
+                register2,
+            }
+            | Instruction::ShiftLeft {
+                register1,
+                register2,
+            }
+            | Instruction::ShiftRight {
+                register1,
+                register2,
+            }
+            | Instruction::SubRegisters {
+                register1,
+                register2,
+            }
+            | Instruction::SubRegistersReversed {
+                register1,
+                register2,
+            }
+            | Instruction::SkipIfEqualRegisters {
+                register1,
+                register2,
+            }
+            | Instruction::SkipNotEqualRegisters {
+                register1,
+                register2,
+            }
+            | Instruction::Draw {
+                register1,
+                register2,
+                ..
+            }
+            | Instruction::DrawExtended {
+                register1,
+                register2,
+            } => smallvec![register1, register2],
+            Instruction::JumpWithOffset(address) => {
+                let register = U4::new((address >> 8) as u8 & 0xF);
+                if *register == 0 {
+                    smallvec![register]
+                } else {
+                    smallvec![U4::new(0), register]
+                }
+            }
+            Instruction::WriteRegistersToMemory { register }
+            | Instruction::SaveRplFlags { register } => (0..=*register).map(U4::new).collect(),
+            Instruction::CallSubroutine(_)
+            | Instruction::ClearScreen
+            | Instruction::EnableHighResolution
+            | Instruction::DisableHighResolution
+            | Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::ExitEmulator
+            | Instruction::SetPlane { .. }
+            | Instruction::Jump(_)
+            | Instruction::Nop(_)
+            | Instruction::LoadLong { .. }
+            | Instruction::LoadRegisterFromDelayTimer { .. }
+            | Instruction::LoadRegisterFromKeyPress { .. }
+            | Instruction::LoadRegistersFromMemory { .. }
+            | Instruction::LoadRplFlags { .. }
+            | Instruction::Random { .. }
+            | Instruction::Return
+            | Instruction::SetIndex(_)
+            | Instruction::SetValue { .. } => SmallVec::new(),
+        }
+    }
+
+    pub fn category(&self) -> InstructionCategory {
+        match self {
+            Instruction::AddValue { .. }
+            | Instruction::AddRegisters { .. }
+            | Instruction::Random { .. }
+            | Instruction::SubRegisters { .. }
+            | Instruction::SubRegistersReversed { .. } => InstructionCategory::Arithmetic,
+            Instruction::And { .. }
+            | Instruction::Or { .. }
+            | Instruction::ShiftLeft { .. }
+            | Instruction::ShiftRight { .. }
+            | Instruction::Xor { .. } => InstructionCategory::Bitwise,
+            Instruction::AddRegisterToIndex { .. }
+            | Instruction::LoadFont { .. }
+            | Instruction::LoadLargeFont { .. }
+            | Instruction::LoadLong { .. }
+            | Instruction::LoadRegistersFromMemory { .. }
+            | Instruction::LoadRegisterFromRegister { .. }
+            | Instruction::LoadRplFlags { .. }
+            | Instruction::SaveRplFlags { .. }
+            | Instruction::SetIndex(_)
+            | Instruction::SetValue { .. }
+            | Instruction::StoreBcdRepresentation { .. }
+            | Instruction::WriteRegistersToMemory { .. } => InstructionCategory::Memory,
+            Instruction::ClearScreen
+            | Instruction::Draw { .. }
+            | Instruction::DrawExtended { .. }
+            | Instruction::EnableHighResolution
+            | Instruction::DisableHighResolution
+            | Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::SetPlane { .. } => InstructionCategory::Display,
+            Instruction::CallSubroutine(_)
+            | Instruction::Jump(_)
+            | Instruction::JumpWithOffset(_)
+            | Instruction::ExitEmulator
+            | Instruction::Return
+            | Instruction::SkipIfEqual { .. }
+            | Instruction::SkipIfEqualRegisters { .. }
+            | Instruction::SkipNotEqualByte { .. }
+            | Instruction::SkipNotEqualRegisters { .. } => InstructionCategory::ControlFlow,
+            Instruction::LoadRegisterFromKeyPress { .. }
+            | Instruction::SkipIfKeyPressed { .. }
+            | Instruction::SkipIfKeyNotPressed { .. } => InstructionCategory::IO,
+            Instruction::LoadDelayTimer { .. }
+            | Instruction::LoadRegisterFromDelayTimer { .. }
+            | Instruction::LoadSoundTimer { .. } => InstructionCategory::Timer,
+            Instruction::Nop(_) => InstructionCategory::Padding,
+        }
+    }
+}
+
+impl TryFrom<u16> for Instruction {
+    type Error = anyhow::Error;
+
+    fn try_from(raw_instruction: u16) -> Result<Self> {
+        Self::try_from_u16(raw_instruction)
+    }
+}
+
+impl TryFrom<[u8; 2]> for Instruction {
+    type Error = anyhow::Error;
+
+    fn try_from([high, low]: [u8; 2]) -> Result<Self> {
+        Self::try_from_bytes(high, low)
+    }
 }
 
 impl Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            Instruction::AddRegisterToIndex { register } => write!(f, "ADD I, V{:X})", **register),
+            Instruction::AddRegisterToIndex { register } => write!(f, "ADD I, V{:X}", **register),
             Instruction::And {
                 register1,
                 register2,
@@ -242,6 +634,18 @@ impl Display for Instruction {
             }
             Instruction::CallSubroutine(addr) => write!(f, "CALL {:0>4X}", **addr),
             Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::DrawExtended {
+                register1,
+                register2,
+            } => write!(f, "DRW V{:X}, V{:X}, 0", **register1, **register2),
+            Instruction::EnableHighResolution => write!(f, "HIGH"),
+            Instruction::DisableHighResolution => write!(f, "LOW"),
+            Instruction::ScrollDown { rows } => write!(f, "SCD {:X}", **rows),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::ExitEmulator => write!(f, "EXIT"),
+            Instruction::SetPlane { planes } => write!(f, "PLANE {:X}", **planes),
+            Instruction::Nop(_) => write!(f, "NOP"),
             Instruction::Draw {
                 register1,
                 register2,
@@ -254,7 +658,9 @@ impl Display for Instruction {
             Instruction::Jump(address) => write!(f, "JP {:0>4X}", address),
             Instruction::JumpWithOffset(address) => write!(f, "JP V0, {:0>4X}", address),
             Instruction::LoadDelayTimer { register } => write!(f, "LD DT, V{:X}", **register),
-            Instruction::LoadFont { register } => write!(f, "LD F, V{:x}", **register),
+            Instruction::LoadFont { register } => write!(f, "LD F, V{:X}", **register),
+            Instruction::LoadLargeFont { register } => write!(f, "LD HF, V{:X}", **register),
+            Instruction::LoadLong { address } => write!(f, "LD I, LONG {:0>4X}", address),
             Instruction::LoadRegisterFromDelayTimer { register } => {
                 write!(f, "LD V{:X}, DT", **register)
             }
@@ -265,6 +671,7 @@ impl Display for Instruction {
                 register1,
                 register2,
             } => write!(f, "LD V{:X}, V{:X}", **register1, **register2),
+            Instruction::LoadRplFlags { register } => write!(f, "LD V{:X}, R", **register),
             Instruction::LoadSoundTimer { register } => write!(f, "LD ST, V{:X}", **register),
             Instruction::Or {
                 register1,
@@ -274,6 +681,7 @@ impl Display for Instruction {
                 write!(f, "RND V{:X}, {:0>2X}", **register, mask)
             }
             Instruction::Return => write!(f, "RET"),
+            Instruction::SaveRplFlags { register } => write!(f, "LD R, V{:X}", **register),
             Instruction::SetIndex(idx) => write!(f, "LD I, {:0>4X}", idx),
             Instruction::SetValue { register, value } => {
                 write!(f, "LD V{:X}, {:0>2X}", **register, value)
@@ -297,7 +705,7 @@ impl Display for Instruction {
                 register1,
                 register2,
             } => {
-                write!(f, "SNE V{:X}, {:X}", **register1, **register2)
+                write!(f, "SNE V{:X}, V{:X}", **register1, **register2)
             }
             Instruction::ShiftLeft {
                 register1,
@@ -334,3 +742,392 @@ impl Display for Instruction {
         }
     }
 }
+
+fn encode_byte(prefix: u8, register: U4, byte: u8) -> u16 {
+    set_nibble_at(join_bytes(prefix << 4, byte), 2, register)
+}
+
+fn encode_registers(prefix: u8, register1: U4, register2: U4, suffix: u8) -> u16 {
+    join_nibbles(prefix, *register1, *register2, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn decoded_instructions_encode_to_the_same_opcode(
+            raw_instruction in any::<u16>().prop_filter("decodable opcode", |raw| {
+                Instruction::try_from_u16(*raw).is_ok()
+            })
+        ) {
+            let instruction = Instruction::try_from_u16(raw_instruction).unwrap();
+            prop_assert_eq!(raw_instruction, instruction.to_u16());
+        }
+
+        #[test]
+        fn decoding_errors_have_a_message(raw_instruction in any::<u16>()) {
+            if let Err(err) = Instruction::try_from_u16(raw_instruction) {
+                prop_assert!(!err.to_string().is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn decoding_any_word_does_not_panic() {
+        for raw_instruction in 0..=u16::MAX {
+            if let Ok(instruction) = Instruction::try_from_u16(raw_instruction) {
+                instruction.to_string();
+                instruction.category();
+                instruction.registers_written();
+            }
+        }
+    }
+
+    #[test]
+    fn every_decodable_instruction_has_a_category() {
+        let expected = [
+            (0x00E0, InstructionCategory::Display),
+            (0x00EE, InstructionCategory::ControlFlow),
+            (0x00C4, InstructionCategory::Display),
+            (0x00FB, InstructionCategory::Display),
+            (0x00FC, InstructionCategory::Display),
+            (0x00FD, InstructionCategory::ControlFlow),
+            (0x00FE, InstructionCategory::Display),
+            (0x00FF, InstructionCategory::Display),
+            (0x1234, InstructionCategory::ControlFlow),
+            (0x2234, InstructionCategory::ControlFlow),
+            (0x3122, InstructionCategory::ControlFlow),
+            (0x4122, InstructionCategory::ControlFlow),
+            (0x5120, InstructionCategory::ControlFlow),
+            (0x6122, InstructionCategory::Memory),
+            (0x7122, InstructionCategory::Arithmetic),
+            (0x8120, InstructionCategory::Memory),
+            (0x8121, InstructionCategory::Bitwise),
+            (0x8122, InstructionCategory::Bitwise),
+            (0x8123, InstructionCategory::Bitwise),
+            (0x8124, InstructionCategory::Arithmetic),
+            (0x8125, InstructionCategory::Arithmetic),
+            (0x8126, InstructionCategory::Bitwise),
+            (0x8127, InstructionCategory::Arithmetic),
+            (0x812E, InstructionCategory::Bitwise),
+            (0x9120, InstructionCategory::ControlFlow),
+            (0xA234, InstructionCategory::Memory),
+            (0xB234, InstructionCategory::ControlFlow),
+            (0xC1FF, InstructionCategory::Arithmetic),
+            (0xD125, InstructionCategory::Display),
+            (0xD120, InstructionCategory::Display),
+            (0xE19E, InstructionCategory::IO),
+            (0xE1A1, InstructionCategory::IO),
+            (0xF301, InstructionCategory::Display),
+            (0xF107, InstructionCategory::Timer),
+            (0xF10A, InstructionCategory::IO),
+            (0xF115, InstructionCategory::Timer),
+            (0xF118, InstructionCategory::Timer),
+            (0xF11E, InstructionCategory::Memory),
+            (0xF129, InstructionCategory::Memory),
+            (0xF130, InstructionCategory::Memory),
+            (0xF133, InstructionCategory::Memory),
+            (0xF155, InstructionCategory::Memory),
+            (0xF165, InstructionCategory::Memory),
+            (0xF175, InstructionCategory::Memory),
+            (0xF185, InstructionCategory::Memory),
+        ];
+
+        for (raw_instruction, category) in expected {
+            let instruction = Instruction::try_from_u16(raw_instruction).unwrap();
+            assert_eq!(
+                category,
+                instruction.category(),
+                "Wrong category for 0x{:0>4X}",
+                raw_instruction
+            );
+        }
+    }
+
+    #[test]
+    fn lenient_decoding_turns_invalid_words_into_nop() {
+        for raw_instruction in [0x0000, 0x0123, 0x5121, 0xFFFF] {
+            let instruction = Instruction::from_u16_lenient(raw_instruction);
+            assert!(matches!(instruction, Instruction::Nop(_)));
+            assert_eq!("NOP", instruction.to_string());
+            assert_eq!(raw_instruction, instruction.to_u16());
+        }
+
+        assert!(matches!(
+            Instruction::from_u16_lenient(0x00E0),
+            Instruction::ClearScreen
+        ));
+    }
+
+    #[test]
+    fn key_skip_instructions_are_decoded() {
+        let skip_if_pressed = Instruction::try_from_u16(0xE59E).unwrap();
+        let skip_if_not_pressed = Instruction::try_from_u16(0xEAA1).unwrap();
+
+        assert!(matches!(
+            skip_if_pressed,
+            Instruction::SkipIfKeyPressed { register } if *register == 0x5
+        ));
+        assert!(matches!(
+            skip_if_not_pressed,
+            Instruction::SkipIfKeyNotPressed { register } if *register == 0xA
+        ));
+        assert_eq!("SKP V5", skip_if_pressed.to_string());
+        assert_eq!("SKNP VA", skip_if_not_pressed.to_string());
+    }
+
+    #[test]
+    fn timer_instructions_are_formatted_as_assembly() {
+        let cases = [
+            (
+                Instruction::LoadRegisterFromDelayTimer {
+                    register: U4::new(6),
+                },
+                "LD V6, DT",
+            ),
+            (
+                Instruction::LoadDelayTimer {
+                    register: U4::new(0xA),
+                },
+                "LD DT, VA",
+            ),
+            (
+                Instruction::LoadSoundTimer {
+                    register: U4::new(3),
+                },
+                "LD ST, V3",
+            ),
+        ];
+
+        for (instruction, expected) in cases {
+            assert_eq!(expected, instruction.to_string());
+        }
+    }
+
+    #[test]
+    fn every_instruction_is_formatted_as_assembly() {
+        let cases = [
+            (0x00E0, "CLS"),
+            (0x00EE, "RET"),
+            (0x00C4, "SCD 4"),
+            (0x00FB, "SCR"),
+            (0x00FC, "SCL"),
+            (0x00FD, "EXIT"),
+            (0x00FE, "LOW"),
+            (0x00FF, "HIGH"),
+            (0x1234, "JP 0234"),
+            (0x2345, "CALL 0345"),
+            (0x31AB, "SE V1, AB"),
+            (0x42CD, "SNE V2, CD"),
+            (0x5340, "SE V3, V4"),
+            (0x6A0F, "LD VA, 0F"),
+            (0x7B10, "ADD VB, 10"),
+            (0x8120, "LD V1, V2"),
+            (0x8121, "OR V1, V2"),
+            (0x8122, "AND V1, V2"),
+            (0x8123, "XOR V1, V2"),
+            (0x8124, "ADD V1, V2"),
+            (0x8125, "SUB V1, V2"),
+            (0x8126, "SHR V1 {, V2}"),
+            (0x8127, "SUBN V1, V2"),
+            (0x812E, "SHL V1 {, V2}"),
+            (0x9560, "SNE V5, V6"),
+            (0xA2F0, "LD I, 02F0"),
+            (0xB300, "JP V0, 0300"),
+            (0xC7FF, "RND V7, FF"),
+            (0xD125, "DRW V1, V2, 5"),
+            (0xD120, "DRW V1, V2, 0"),
+            (0xE89E, "SKP V8"),
+            (0xE9A1, "SKNP V9"),
+            (0xF301, "PLANE 3"),
+            (0xF607, "LD V6, DT"),
+            (0xF00A, "LD V0, K"),
+            (0xFA15, "LD DT, VA"),
+            (0xF318, "LD ST, V3"),
+            (0xF21E, "ADD I, V2"),
+            (0xFB29, "LD F, VB"),
+            (0xF430, "LD HF, V4"),
+            (0xFC33, "LD B, VC"),
+            (0xFD55, "LD [I], VD"),
+            (0xFE65, "LD VE, [I]"),
+            (0xF775, "LD R, V7"),
+            (0xF785, "LD V7, R"),
+        ];
+
+        for (raw_instruction, expected) in cases {
+            let instruction = Instruction::try_from_u16(raw_instruction).unwrap();
+            assert_eq!(
+                expected,
+                instruction.to_string(),
+                "Wrong assembly for 0x{:0>4X}",
+                raw_instruction
+            );
+        }
+
+        assert_eq!(
+            "LD I, LONG BEEF",
+            Instruction::LoadLong { address: 0xBEEF }.to_string()
+        );
+        assert_eq!("NOP", Instruction::Nop(0x0000).to_string());
+    }
+
+    #[test]
+    fn registers_written_by_each_instruction() {
+        let expected: Vec<(u16, Vec<u8>)> = vec![
+            (0x00E0, vec![]),
+            (0x00EE, vec![]),
+            (0x00C1, vec![]),
+            (0x00FB, vec![]),
+            (0x00FC, vec![]),
+            (0x00FD, vec![]),
+            (0x00FE, vec![]),
+            (0x00FF, vec![]),
+            (0x1234, vec![]),
+            (0x2234, vec![]),
+            (0x3142, vec![]),
+            (0x4142, vec![]),
+            (0x5120, vec![]),
+            (0x6142, vec![1]),
+            (0x7142, vec![1]),
+            (0x8120, vec![1]),
+            (0x8121, vec![1, 0xF]),
+            (0x8122, vec![1, 0xF]),
+            (0x8123, vec![1, 0xF]),
+            (0x8124, vec![1, 0xF]),
+            (0x8125, vec![1, 0xF]),
+            (0x8126, vec![1, 0xF]),
+            (0x8127, vec![1, 0xF]),
+            (0x812E, vec![1, 0xF]),
+            (0x9120, vec![]),
+            (0xA234, vec![]),
+            (0xB234, vec![]),
+            (0xC1FF, vec![1]),
+            (0xD125, vec![0xF]),
+            (0xD120, vec![0xF]),
+            (0xE19E, vec![]),
+            (0xE1A1, vec![]),
+            (0xF301, vec![]),
+            (0xF107, vec![1]),
+            (0xF10A, vec![1]),
+            (0xF115, vec![]),
+            (0xF118, vec![]),
+            (0xF11E, vec![]),
+            (0xF129, vec![]),
+            (0xF130, vec![]),
+            (0xF133, vec![]),
+            (0xF155, vec![]),
+            (0xF265, vec![0, 1, 2]),
+            (0xF175, vec![]),
+            (0xF385, vec![0, 1, 2, 3]),
+        ];
+
+        for (raw_instruction, registers) in expected {
+            let instruction = Instruction::try_from_u16(raw_instruction).unwrap();
+            let written = instruction
+                .registers_written()
+                .iter()
+                .map(|register| **register)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                registers, written,
+                "Wrong registers for 0x{:0>4X}",
+                raw_instruction
+            );
+        }
+
+        let load_long = Instruction::LoadLong { address: 0x1234 };
+        assert!(load_long.registers_written().is_empty());
+        assert_eq!(LOAD_LONG_OPCODE, load_long.to_u16());
+    }
+
+    #[test]
+    fn registers_read_by_each_instruction() {
+        let expected: Vec<(u16, Vec<u8>)> = vec![
+            (0x00E0, vec![]),
+            (0x00EE, vec![]),
+            (0x00C1, vec![]),
+            (0x00FB, vec![]),
+            (0x00FC, vec![]),
+            (0x00FD, vec![]),
+            (0x00FE, vec![]),
+            (0x00FF, vec![]),
+            (0x1234, vec![]),
+            (0x2234, vec![]),
+            (0x3142, vec![1]),
+            (0x4142, vec![1]),
+            (0x5120, vec![1, 2]),
+            (0x6142, vec![]),
+            (0x7142, vec![1]),
+            (0x8120, vec![2]),
+            (0x8121, vec![1, 2]),
+            (0x8122, vec![1, 2]),
+            (0x8123, vec![1, 2]),
+            (0x8124, vec![1, 2]),
+            (0x8125, vec![1, 2]),
+            (0x8126, vec![1, 2]),
+            (0x8127, vec![1, 2]),
+            (0x812E, vec![1, 2]),
+            (0x9120, vec![1, 2]),
+            (0xA234, vec![]),
+            (0xB034, vec![0]),
+            (0xB234, vec![0, 2]),
+            (0xC1FF, vec![]),
+            (0xD125, vec![1, 2]),
+            (0xD120, vec![1, 2]),
+            (0xE19E, vec![1]),
+            (0xE1A1, vec![1]),
+            (0xF301, vec![]),
+            (0xF107, vec![]),
+            (0xF10A, vec![]),
+            (0xF115, vec![1]),
+            (0xF118, vec![1]),
+            (0xF11E, vec![1]),
+            (0xF129, vec![1]),
+            (0xF130, vec![1]),
+            (0xF133, vec![1]),
+            (0xF255, vec![0, 1, 2]),
+            (0xF165, vec![]),
+            (0xF375, vec![0, 1, 2, 3]),
+            (0xF185, vec![]),
+        ];
+
+        for (raw_instruction, registers) in expected {
+            let instruction = Instruction::try_from_u16(raw_instruction).unwrap();
+            let read = instruction
+                .registers_read()
+                .iter()
+                .map(|register| **register)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                registers, read,
+                "Wrong registers for 0x{:0>4X}",
+                raw_instruction
+            );
+        }
+    }
+
+    #[test]
+    fn instructions_are_decoded_from_bytes() {
+        let instruction = Instruction::try_from_bytes(0xA2, 0x2A).unwrap();
+        assert!(matches!(instruction, Instruction::SetIndex(0x22A)));
+
+        let instruction = Instruction::try_from([0xD0, 0x1F]).unwrap();
+        assert_eq!("DRW V0, V1, F", instruction.to_string());
+
+        assert!(Instruction::try_from([0xFF, 0xFF]).is_err());
+    }
+
+    #[test]
+    fn instructions_are_converted_from_words() {
+        let instruction: Instruction = 0x00E0.try_into().unwrap();
+        assert!(matches!(instruction, Instruction::ClearScreen));
+
+        let instruction = Instruction::try_from(0xA22A_u16).unwrap();
+        assert!(matches!(instruction, Instruction::SetIndex(0x22A)));
+
+        assert!(Instruction::try_from(0xFFFF_u16).is_err());
+    }
+}
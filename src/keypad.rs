@@ -1,33 +1,139 @@
+use std::collections::{HashSet, VecDeque};
+
 pub trait Keypad {
     fn is_key_down(&self, key: u8) -> bool;
     fn get_pressed_key(&self) -> Option<u8>;
+
+    /// Called by the cpu at the start of every tick, before the instruction is executed
+    fn tick(&mut self) {}
 }
 
 pub struct MockKeypad {
-    pub value: Option<u8>,
+    pub pressed_keys: HashSet<u8>,
 }
 
 impl MockKeypad {
     pub fn from_value(value: u8) -> Self {
-        Self { value: Some(value) }
+        Self::from_keys(&[value])
+    }
+
+    pub fn from_keys(keys: &[u8]) -> Self {
+        Self {
+            pressed_keys: keys.iter().copied().collect(),
+        }
     }
 }
 
 impl Default for MockKeypad {
     fn default() -> Self {
-        Self { value: None }
+        Self {
+            pressed_keys: HashSet::new(),
+        }
     }
 }
 
 impl Keypad for MockKeypad {
     fn is_key_down(&self, key: u8) -> bool {
-        let Some(value) = self.value else {
-            return false;
-        };
-        value == key
+        self.pressed_keys.contains(&key)
+    }
+
+    /// Returns the lowest of the held keys so the result does not depend on the set order
+    fn get_pressed_key(&self) -> Option<u8> {
+        self.pressed_keys.iter().min().copied()
+    }
+}
+
+/// Replays recorded key events. An event changes the held key starting with the tick whose number
+/// matches the tick count of the cpu, the first tick being 1. `None` releases the key.
+#[derive(Default)]
+pub struct ReplayKeypad {
+    events: VecDeque<(u64, Option<u8>)>,
+    current_tick: u64,
+    current_key: Option<u8>,
+}
+
+impl ReplayKeypad {
+    pub fn from_events(events: &[(u64, Option<u8>)]) -> Self {
+        let mut events = events.to_vec();
+        events.sort_by_key(|(tick, _)| *tick);
+
+        Self {
+            events: events.into(),
+            current_tick: 0,
+            current_key: None,
+        }
+    }
+}
+
+impl Keypad for ReplayKeypad {
+    fn is_key_down(&self, key: u8) -> bool {
+        self.current_key == Some(key)
     }
 
     fn get_pressed_key(&self) -> Option<u8> {
-        self.value
+        self.current_key
+    }
+
+    fn tick(&mut self) {
+        self.current_tick += 1;
+        while let Some(&(tick, key)) = self.events.front() {
+            if tick > self.current_tick {
+                break;
+            }
+
+            self.current_key = key;
+            self.events.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_key_is_down_without_pressed_keys() {
+        let keypad = MockKeypad::default();
+
+        assert!((0..16).all(|key| !keypad.is_key_down(key)));
+        assert_eq!(None, keypad.get_pressed_key());
+    }
+
+    #[test]
+    fn single_key_is_down() {
+        let keypad = MockKeypad::from_value(0xA);
+
+        assert!(keypad.is_key_down(0xA));
+        assert!(!keypad.is_key_down(0xB));
+        assert_eq!(Some(0xA), keypad.get_pressed_key());
+    }
+
+    #[test]
+    fn replay_keypad_applies_events_at_their_tick() {
+        let mut keypad = ReplayKeypad::from_events(&[(7, None), (5, Some(0x3))]);
+
+        for _ in 0..4 {
+            keypad.tick();
+        }
+        assert_eq!(None, keypad.get_pressed_key());
+
+        keypad.tick();
+        assert!(keypad.is_key_down(0x3));
+        keypad.tick();
+        assert_eq!(Some(0x3), keypad.get_pressed_key());
+
+        keypad.tick();
+        assert!(!keypad.is_key_down(0x3));
+        assert_eq!(None, keypad.get_pressed_key());
+    }
+
+    #[test]
+    fn two_keys_are_held_simultaneously() {
+        let keypad = MockKeypad::from_keys(&[0x5, 0x2]);
+
+        assert!(keypad.is_key_down(0x2));
+        assert!(keypad.is_key_down(0x5));
+        assert!(!keypad.is_key_down(0x3));
+        assert_eq!(Some(0x2), keypad.get_pressed_key());
     }
 }
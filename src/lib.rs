@@ -0,0 +1,12 @@
+pub mod bits;
+pub mod cpu;
+pub mod display;
+pub mod instruction;
+pub mod keypad;
+pub mod memory;
+pub mod rom;
+pub mod timer;
+
+use bits::U4;
+use instruction::Instruction;
+use memory::MEMORY_START;
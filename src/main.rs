@@ -1,29 +1,41 @@
-mod memory;
-
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
+use args::Args;
 use bits::U4;
-use cpu::{Cpu, INSTRUCTION_RATE};
+use chip8_emulator::{bits, cpu, display, instruction, keypad, memory, rom, timer};
+use config::{EmulatorConfig, CONFIG_PATH};
+use cpu::{format_checksum, CompatibilityMode, Cpu, CpuState, CpuStateDiff};
 use display::Display;
 use egui_extras::{Column, TableBuilder};
 use egui_macroquad::egui;
-use instruction::Instruction;
+use instruction::{Instruction, InstructionCategory};
 use keypad::Keypad;
-use memory::{MemoryAddress, MEMORY_START};
-use rom::Rom;
+use memory::{Memory, MemoryAddress, MEMORY_SIZE};
+use rom::{Rom, RomMetadata};
+use timer::Timer;
 
-mod bits;
-mod cpu;
-mod display;
-mod instruction;
-mod keypad;
-mod rom;
-mod timer;
+mod args;
+mod config;
 
 use macroquad::prelude::*;
 
-use crate::{bits::join_bytes, memory::MEMORY_SIZE};
+const MAX_SNAPSHOTS: usize = 100;
+const MAX_OUTPUT_LINES: usize = 500;
+const MAX_TRACE_ENTRIES: usize = 10_000;
+const STATS_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const VISIBLE_TRACE_ENTRIES: usize = 10;
+const DEFAULT_TICKS_PER_FRAME: u32 = 10;
+const FRAME_RATE: u32 = 60;
+const SCREENSHOT_SCALE: u32 = 4;
+/// Panel sizes relative to the window size
+const SIDE_PANEL_WIDTH_FRACTION: f32 = 0.21;
+const MEMORY_PANEL_HEIGHT_FRACTION: f32 = 0.37;
+const PC_AND_MATCH_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 165, 0);
+const CHANGED_MEMORY_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 128, 128);
 
 struct MacroquadKeypad {
     keys: Vec<KeyCode>,
@@ -57,6 +69,37 @@ impl Default for MacroquadKeypad {
 }
 
 impl MacroquadKeypad {
+    /// Creates a keypad from a mapping of 16 keys for the values 0x0 to 0xF
+    fn from_mapping(mapping: &str) -> Result<Self> {
+        let keys = mapping
+            .chars()
+            .map(|c| {
+                keycode_from_char(c)
+                    .ok_or_else(|| anyhow!("Unsupported key '{}' in key mapping", c))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if keys.len() != 16 {
+            return Err(anyhow!(
+                "Key mapping must contain 16 keys, got {}",
+                keys.len()
+            ));
+        }
+
+        if keys
+            .iter()
+            .enumerate()
+            .any(|(idx, key)| keys[..idx].contains(key))
+        {
+            return Err(anyhow!("Key mapping '{}' contains duplicate keys", mapping));
+        }
+
+        Ok(Self {
+            keys,
+            values: (0..16).collect(),
+        })
+    }
+
     fn convert_keycode(&self, key: KeyCode) -> Option<u8> {
         let Some(idx) = self.keys.iter().position(|&k| k == key) else {
             return None;
@@ -71,6 +114,50 @@ impl MacroquadKeypad {
     }
 }
 
+fn keycode_from_char(c: char) -> Option<KeyCode> {
+    let key = match c.to_ascii_uppercase() {
+        '0' => KeyCode::Key0,
+        '1' => KeyCode::Key1,
+        '2' => KeyCode::Key2,
+        '3' => KeyCode::Key3,
+        '4' => KeyCode::Key4,
+        '5' => KeyCode::Key5,
+        '6' => KeyCode::Key6,
+        '7' => KeyCode::Key7,
+        '8' => KeyCode::Key8,
+        '9' => KeyCode::Key9,
+        'A' => KeyCode::A,
+        'B' => KeyCode::B,
+        'C' => KeyCode::C,
+        'D' => KeyCode::D,
+        'E' => KeyCode::E,
+        'F' => KeyCode::F,
+        'G' => KeyCode::G,
+        'H' => KeyCode::H,
+        'I' => KeyCode::I,
+        'J' => KeyCode::J,
+        'K' => KeyCode::K,
+        'L' => KeyCode::L,
+        'M' => KeyCode::M,
+        'N' => KeyCode::N,
+        'O' => KeyCode::O,
+        'P' => KeyCode::P,
+        'Q' => KeyCode::Q,
+        'R' => KeyCode::R,
+        'S' => KeyCode::S,
+        'T' => KeyCode::T,
+        'U' => KeyCode::U,
+        'V' => KeyCode::V,
+        'W' => KeyCode::W,
+        'X' => KeyCode::X,
+        'Y' => KeyCode::Y,
+        'Z' => KeyCode::Z,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
 impl Keypad for MacroquadKeypad {
     fn is_key_down(&self, value: u8) -> bool {
         let key = self.convert_value(value);
@@ -91,34 +178,257 @@ impl Keypad for MacroquadKeypad {
 
 #[derive(PartialEq)]
 enum CpuExecution {
+    /// No rom has been loaded successfully yet
+    Unloaded,
     Paused,
     Running,
+    /// The cpu returned an error, only a restart can continue execution
+    Crashed,
+    /// The rom stopped itself with 00FD, only a restart can continue execution
+    Exited,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum EditableRegister {
+    ProgramCounter,
+    Index,
+    Variable(u8),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Breakpoint {
+    AtAddress(MemoryAddress),
+    WhenRegister { register: U4, value: u8 },
+}
+
+impl Breakpoint {
+    /// Register breakpoints only trigger when the register changes to the value, so execution can
+    /// continue while the register keeps it
+    fn is_hit<T: Keypad + Default>(&self, registers_before: &[u8; 16], cpu: &Cpu<T>) -> bool {
+        match *self {
+            Breakpoint::AtAddress(address) => address == cpu.pc_address(),
+            Breakpoint::WhenRegister { register, value } => {
+                registers_before[*register as usize] != value && cpu.get_register(register) == value
+            }
+        }
+    }
+}
+
+fn register_values<T: Keypad + Default>(cpu: &Cpu<T>) -> [u8; 16] {
+    let mut registers = [0; 16];
+    for (index, value) in registers.iter_mut().enumerate() {
+        *value = cpu.get_register(U4::new(index as u8));
+    }
+    registers
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::AtAddress(address) => write!(f, "PC == 0x{:0>4X}", **address),
+            Breakpoint::WhenRegister { register, value } => {
+                write!(f, "V{:X} == 0x{:0>2X}", **register, value)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BreakpointKind {
+    Address,
+    Register,
+}
+
+/// Rom in the rom directory, with the name from its metadata if there is one
+struct RomEntry {
+    path: String,
+    name: Option<String>,
+}
+
+/// An executed instruction with the variable registers after its execution
+struct TraceEntry {
+    address: MemoryAddress,
+    raw_instruction: u16,
+    instruction: Option<Instruction>,
+    registers: [u8; 16],
+}
+
+impl TraceEntry {
+    fn mnemonic(&self) -> String {
+        match self.instruction {
+            Some(instruction) => instruction.to_string(),
+            None => "???".to_string(),
+        }
+    }
+}
+
+/// Executed instructions since the rom was loaded and the measured instruction rate
+struct ExecutionStats {
+    start_time: Instant,
+    total_ticks: u64,
+    last_ticks_sample: u64,
+    last_sample_time: Instant,
+    /// Instructions per second measured over the last sample interval
+    ticks_per_second: f64,
+}
+
+impl ExecutionStats {
+    fn new(now: Instant) -> Self {
+        Self {
+            start_time: now,
+            total_ticks: 0,
+            last_ticks_sample: 0,
+            last_sample_time: now,
+            ticks_per_second: 0.,
+        }
+    }
+
+    fn record_tick(&mut self) {
+        self.total_ticks += 1;
+    }
+
+    /// Measures the instruction rate once `STATS_SAMPLE_INTERVAL` has passed since the last sample
+    fn update(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_sample_time);
+        if elapsed < STATS_SAMPLE_INTERVAL {
+            return;
+        }
+
+        let ticks = self.total_ticks - self.last_ticks_sample;
+        self.ticks_per_second = ticks as f64 / elapsed.as_secs_f64();
+        self.last_ticks_sample = self.total_ticks;
+        self.last_sample_time = now;
+    }
+
+    fn summary(&self, now: Instant) -> String {
+        let session = now.saturating_duration_since(self.start_time).as_secs();
+        format!(
+            "Instructions: {} | Speed: {:.0} Hz | Session: {:0>2}:{:0>2}:{:0>2}",
+            self.total_ticks,
+            self.ticks_per_second,
+            session / 3600,
+            session / 60 % 60,
+            session % 60
+        )
+    }
+}
+
+/// Positions of the panels and the chip8 screen for a window size
+#[derive(Debug, PartialEq)]
+struct ScreenLayout {
+    side_panel_width: f32,
+    memory_panel_height: f32,
+    display_x: f32,
+    display_y: f32,
+    /// Size of a pixel in the low resolution mode
+    pixel_size: f32,
+}
+
+impl ScreenLayout {
+    /// Centers the largest screen with whole pixels in the space between the panels
+    fn for_window(width: f32, height: f32) -> Self {
+        let side_panel_width = (width * SIDE_PANEL_WIDTH_FRACTION).round();
+        let memory_panel_height = (height * MEMORY_PANEL_HEIGHT_FRACTION).round();
+        let available_width = (width - 2. * side_panel_width).max(0.);
+        let available_height = (height - memory_panel_height).max(0.);
+        let pixel_size = (available_width / 64.).min(available_height / 32.).floor();
+
+        Self {
+            side_panel_width,
+            memory_panel_height,
+            display_x: side_panel_width + (available_width - 64. * pixel_size) / 2.,
+            display_y: (available_height - 32. * pixel_size) / 2.,
+            pixel_size,
+        }
+    }
+}
+
+struct RegisterEdit {
+    register: EditableRegister,
+    input: String,
 }
 
 struct UiState {
     cpu: Cpu<MacroquadKeypad>,
     execution: CpuExecution,
+    ticks_per_frame: u32,
     current_rom: String,
-    has_failed: bool,
-    has_ticked: bool,
+    font_path: String,
+    /// Font that replaces the built-in font of every loaded rom
+    custom_font: Option<[u8; 80]>,
+    /// Program counter the instruction panel was last scrolled to
+    last_drawn_pc: Option<u16>,
+    goto_address: String,
+    /// Address the instruction panel scrolls to in the next frame
+    scroll_target: Option<u16>,
     output: Vec<String>,
+    max_output_lines: usize,
+    hidden_output_lines: usize,
     memory_filter: String,
     breakpoint_input: String,
-    breakpoint_addresses: Vec<u16>,
+    breakpoint_kind: BreakpointKind,
+    breakpoint_register: U4,
+    breakpoints: Vec<Breakpoint>,
+    replay_seed: bool,
+    disassembly: Vec<(MemoryAddress, u16, Result<Instruction>)>,
+    disassembly_dirty: bool,
+    /// Copy of the memory shown in the memory grid, cleared after instructions that write to memory
+    memory_snapshot: Option<Memory>,
+    /// Search filter and the matching byte indexes in the memory snapshot
+    memory_matches: Option<(String, HashSet<MemoryAddress>)>,
+    /// Number of matched byte sequences, a sequence can span several highlighted bytes
+    memory_match_count: usize,
+    register_edit: Option<RegisterEdit>,
+    modified_registers: HashSet<EditableRegister>,
+    snapshots: VecDeque<CpuState>,
+    /// Recently executed instructions, the oldest entries are dropped after `MAX_TRACE_ENTRIES`
+    trace: VecDeque<TraceEntry>,
+    trace_path: String,
+    stats: ExecutionStats,
+    last_diff: Option<CpuStateDiff>,
+    config: EmulatorConfig,
+    config_path: String,
+    roms: Vec<RomEntry>,
+    fullscreen: bool,
 }
 
 impl Default for UiState {
     fn default() -> Self {
         Self {
             cpu: Cpu::default(),
-            execution: CpuExecution::Paused,
+            execution: CpuExecution::Unloaded,
+            ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
             current_rom: "".to_string(),
-            has_failed: true,
-            has_ticked: false,
+            font_path: "".to_string(),
+            custom_font: None,
+            last_drawn_pc: None,
+            goto_address: "".to_string(),
+            scroll_target: None,
             output: Vec::new(),
+            max_output_lines: MAX_OUTPUT_LINES,
+            hidden_output_lines: 0,
             memory_filter: "".to_string(),
             breakpoint_input: "".to_string(),
-            breakpoint_addresses: Vec::new(),
+            breakpoint_kind: BreakpointKind::Address,
+            breakpoint_register: U4::new(0),
+            breakpoints: Vec::new(),
+            replay_seed: false,
+            disassembly: Vec::new(),
+            disassembly_dirty: true,
+            memory_snapshot: None,
+            memory_matches: None,
+            memory_match_count: 0,
+            register_edit: None,
+            modified_registers: HashSet::new(),
+            snapshots: VecDeque::new(),
+            trace: VecDeque::new(),
+            trace_path: "trace.csv".to_string(),
+            stats: ExecutionStats::new(Instant::now()),
+            last_diff: None,
+            config: EmulatorConfig::default(),
+            config_path: CONFIG_PATH.to_string(),
+            roms: Vec::new(),
+            fullscreen: false,
         }
     }
 }
@@ -134,98 +444,436 @@ impl UiState {
             return;
         };
 
-        let cpu = Cpu::from_rom(rom)
+        let warnings = rom
+            .validate()
+            .with_context(|| format!("Failed validating rom '{}'", rom_path));
+        self.handle_result(&warnings);
+        let Ok(warnings) = warnings else {
+            return;
+        };
+
+        let mut cpu_config = self.config.cpu_config();
+        if let Some(mode) = rom.metadata.as_ref().and_then(|m| m.compatibility_mode) {
+            cpu_config.set_compatibility_mode(mode);
+        }
+
+        let cpu = Cpu::from_rom_with_config(rom, cpu_config)
             .with_context(|| format!("Failed loading rom '{}' into memory", rom_path));
 
         self.handle_result(&cpu);
-        let Ok(cpu) = cpu else {
+        let Ok(mut cpu) = cpu else {
+            return;
+        };
+
+        let keypad = MacroquadKeypad::from_mapping(&self.config.key_mapping)
+            .with_context(|| "Failed applying the key mapping");
+        self.handle_result(&keypad);
+        let Ok(keypad) = keypad else {
             return;
         };
 
+        cpu.set_keypad(keypad);
+        if let Some(font) = self.custom_font {
+            let result = cpu.memory_mut().load_custom_font(&font);
+            self.handle_result(&result);
+            if result.is_err() {
+                return;
+            }
+        }
+
         *self = Self {
             cpu,
-            has_failed: false,
+            execution: CpuExecution::Paused,
             current_rom: rom_path.to_string(),
-            has_ticked: true,
-            breakpoint_addresses: self.breakpoint_addresses.clone(),
+            breakpoints: self.breakpoints.clone(),
+            replay_seed: self.replay_seed,
+            font_path: std::mem::take(&mut self.font_path),
+            trace_path: std::mem::take(&mut self.trace_path),
+            custom_font: self.custom_font,
+            config: std::mem::take(&mut self.config),
+            config_path: std::mem::take(&mut self.config_path),
+            roms: std::mem::take(&mut self.roms),
+            fullscreen: self.fullscreen,
+            max_output_lines: self.max_output_lines,
+            ticks_per_frame: self.ticks_per_frame,
             ..Default::default()
         };
+
+        if self.config.add_recent_rom(rom_path) {
+            self.save_config();
+        }
+
+        for warning in warnings {
+            self.push_output(warning.to_string());
+        }
+    }
+
+    fn load_font(&mut self) -> Result<()> {
+        let data = std::fs::read(&self.font_path)
+            .with_context(|| format!("Failed reading font '{}'", self.font_path))?;
+        let font: [u8; 80] = data.as_slice().try_into().map_err(|_| {
+            anyhow!(
+                "Font '{}' must contain exactly 80 bytes, got {}",
+                self.font_path,
+                data.len()
+            )
+        })?;
+
+        self.cpu.memory_mut().load_custom_font(&font)?;
+        self.custom_font = Some(font);
+        self.memory_snapshot = None;
+        self.disassembly_dirty = true;
+        Ok(())
     }
 
     fn restart(&mut self) {
+        let rpl_flags = self.cpu.rpl_flags;
+        let seed = self.cpu.seed();
         self.load_rom(&self.current_rom.clone());
-        self.execution = CpuExecution::Paused;
+        self.cpu.rpl_flags = rpl_flags;
+        if self.replay_seed {
+            self.cpu.set_seed(seed);
+        }
+    }
+
+    /// Runs the cpu for `ticks_per_frame` ticks, stopping early when it pauses
+    fn run_frame(&mut self) {
+        for _ in 0..self.ticks_per_frame {
+            if !self.is_running() {
+                break;
+            }
+            self.handle_tick();
+        }
     }
 
     fn handle_tick(&mut self) {
+        if self.execution == CpuExecution::Paused {
+            if self.snapshots.len() == MAX_SNAPSHOTS {
+                self.snapshots.pop_front();
+            }
+            self.snapshots.push_back(self.cpu.save_state());
+        } else {
+            self.snapshots.clear();
+        }
+
+        let address = self.cpu.pc_address();
+        let raw_instruction = self
+            .cpu
+            .memory()
+            .read_instruction_checked(address)
+            .unwrap_or_default();
+        if let Ok(
+            Instruction::WriteRegistersToMemory { .. } | Instruction::StoreBcdRepresentation { .. },
+        ) = Instruction::try_from_u16(raw_instruction)
+        {
+            self.disassembly_dirty = true;
+            self.memory_snapshot = None;
+        }
+
+        let registers_before = register_values(&self.cpu);
         let res = self.cpu.tick();
-        self.has_ticked = true;
         self.handle_result(&res);
+        if res.is_err() {
+            self.last_diff = None;
+            return;
+        }
+        self.record_trace(address, raw_instruction);
+        self.stats.record_tick();
+        self.last_diff = match (&self.execution, self.snapshots.back()) {
+            (CpuExecution::Paused, Some(before)) => {
+                Some(CpuState::diff(before, &self.cpu.save_state()))
+            }
+            _ => None,
+        };
         if self
-            .breakpoint_addresses
-            .contains(&*self.cpu.program_counter)
+            .breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.is_hit(&registers_before, &self.cpu))
         {
             self.execution = CpuExecution::Paused;
         }
+        if self.cpu.has_exited() {
+            self.push_output("ROM exited cleanly".to_string());
+            self.execution = CpuExecution::Exited;
+        }
+    }
+
+    fn record_trace(&mut self, address: MemoryAddress, raw_instruction: u16) {
+        if self.trace.len() == MAX_TRACE_ENTRIES {
+            self.trace.pop_front();
+        }
+        self.trace.push_back(TraceEntry {
+            address,
+            raw_instruction,
+            instruction: self.cpu.last_instruction(),
+            registers: register_values(&self.cpu),
+        });
+    }
+
+    /// Writes the trace as CSV, gzip compressed when the path ends with `.csv.gz`
+    fn export_trace(&self, path: &str) -> Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed creating trace file '{}'", path))?;
+        let writer = std::io::BufWriter::new(file);
+
+        if path.ends_with(".csv.gz") {
+            let mut encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+            write_trace_csv(&mut encoder, &self.trace)?;
+            encoder.finish()?.flush()?;
+        } else {
+            let mut writer = writer;
+            write_trace_csv(&mut writer, &self.trace)?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn step_back(&mut self) {
+        let Some(state) = self.snapshots.pop_back() else {
+            return;
+        };
+
+        let result = self.cpu.load_state(state);
+        self.handle_result(&result);
+        if result.is_ok() {
+            self.trace.pop_back();
+        }
+        self.disassembly_dirty = true;
+        self.memory_snapshot = None;
+        self.last_diff = None;
+    }
+
+    fn can_step_back(&self) -> bool {
+        self.is_paused() && !self.snapshots.is_empty()
     }
 
     fn handle_result<T>(&mut self, result: &Result<T>) {
         if let Err(ref err) = result {
-            self.output.push(format!("{:?}", err));
-            self.has_failed = true;
+            self.push_output(format!("{:?}", err));
+            self.execution = CpuExecution::Crashed;
         }
     }
 
     fn is_paused(&self) -> bool {
-        !self.has_failed && self.execution == CpuExecution::Paused
+        self.execution == CpuExecution::Paused
     }
 
     fn is_running(&self) -> bool {
-        !self.has_failed && self.execution == CpuExecution::Running
+        self.execution == CpuExecution::Running
+    }
+
+    /// Switches between running and paused, an unloaded, crashed or exited cpu stays unchanged
+    fn toggle_execution(&mut self) {
+        self.execution = match self.execution {
+            CpuExecution::Paused => CpuExecution::Running,
+            CpuExecution::Running => CpuExecution::Paused,
+            CpuExecution::Unloaded => CpuExecution::Unloaded,
+            CpuExecution::Crashed => CpuExecution::Crashed,
+            CpuExecution::Exited => CpuExecution::Exited,
+        };
     }
 
     fn can_restart(&self) -> bool {
         self.current_rom != ""
     }
 
+    /// Parses the breakpoint form, a 4 digit hex address or a 2 digit hex register value
+    fn parse_breakpoint_input(&self) -> Option<Breakpoint> {
+        let digits = match self.breakpoint_kind {
+            BreakpointKind::Address => 4,
+            BreakpointKind::Register => 2,
+        };
+        if self.breakpoint_input.len() != digits {
+            return None;
+        }
+
+        let value = u16::from_str_radix(&self.breakpoint_input, 16).ok()?;
+        let breakpoint = match self.breakpoint_kind {
+            BreakpointKind::Address => Breakpoint::AtAddress(MemoryAddress::from_u16(value)),
+            BreakpointKind::Register => Breakpoint::WhenRegister {
+                register: self.breakpoint_register,
+                value: value as u8,
+            },
+        };
+        Some(breakpoint)
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Copies the memory if the snapshot was invalidated and searches it again when the snapshot or the
+    /// filter changed. `memory_matches` is `None` if the filter is invalid.
+    fn refresh_memory_snapshot(&mut self) {
+        if self.memory_snapshot.is_none() {
+            self.memory_matches = None;
+        }
+        let memory = self
+            .memory_snapshot
+            .get_or_insert_with(|| self.cpu.memory().clone());
+
+        if matches!(&self.memory_matches, Some((filter, _)) if *filter == self.memory_filter) {
+            return;
+        }
+
+        let search = handle_byte_search_conversion(&self.memory_filter).ok();
+        let starts = search
+            .as_ref()
+            .map(|search| find_byte_search(search, memory));
+        self.memory_match_count = starts.as_ref().map_or(0, Vec::len);
+        self.memory_matches = search.zip(starts).map(|(search, starts)| {
+            let matches = compute_byte_indexes_to_highlight(&starts, search.len());
+            (self.memory_filter.clone(), matches)
+        });
+    }
+
+    /// Scrolls the instruction panel to the address entered in the "Go to" field
+    fn apply_goto_address(&mut self) -> Result<()> {
+        let address = parse_address(self.goto_address.trim())?;
+        self.scroll_target = Some(address);
+        Ok(())
+    }
+
     fn clear_output(&mut self) {
         self.output.clear();
+        self.hidden_output_lines = 0;
+    }
+
+    /// Adds a line to the output, dropping the oldest lines beyond `max_output_lines`
+    fn push_output(&mut self, line: String) {
+        self.output.push(line);
+        if self.output.len() > self.max_output_lines {
+            let excess = self.output.len() - self.max_output_lines;
+            self.output.drain(..excess);
+            self.hidden_output_lines += excess;
+        }
+    }
+
+    fn refresh_roms(&mut self) {
+        let directory = &self.config.rom_directory;
+        match find_roms(Path::new(directory))
+            .with_context(|| format!("Failed reading rom directory '{}'", directory))
+        {
+            Ok(mut roms) => {
+                roms.sort();
+                self.roms = roms
+                    .into_iter()
+                    .map(|path| {
+                        let name = RomMetadata::load_sidecar(&path)
+                            .ok()
+                            .flatten()
+                            .and_then(|metadata| metadata.name);
+                        RomEntry { path, name }
+                    })
+                    .collect();
+            }
+            Err(err) => {
+                self.roms.clear();
+                self.push_output(format!("{:?}", err));
+            }
+        }
+    }
+
+    fn save_config(&mut self) {
+        if let Err(err) = self.config.save(&self.config_path) {
+            self.push_output(format!("{:?}", err));
+        }
+    }
+
+    fn register_value(&self, register: EditableRegister) -> u16 {
+        match register {
+            EditableRegister::ProgramCounter => self.cpu.pc(),
+            EditableRegister::Index => self.cpu.index_value(),
+            EditableRegister::Variable(idx) => self.cpu.get_register(U4::new(idx)) as u16,
+        }
+    }
+
+    fn apply_register_edit(&mut self, register: EditableRegister, input: &str) -> Result<()> {
+        let input = input.trim();
+        match register {
+            EditableRegister::ProgramCounter => {
+                let address = MemoryAddress::from_u16(parse_address(input)?);
+                self.cpu.set_pc(*address.align_to_instruction());
+            }
+            EditableRegister::Index => {
+                let value = parse_address(input)?;
+                self.cpu.set_index(value);
+            }
+            EditableRegister::Variable(idx) => {
+                let value = u8::from_str_radix(input, 16)
+                    .with_context(|| format!("Invalid register value '{}'", input))?;
+                self.cpu.set_register(U4::new(idx), value);
+            }
+        }
+
+        self.modified_registers.insert(register);
+        Ok(())
+    }
+
+    fn refresh_disassembly(&mut self) {
+        let lenient = self.cpu.config().lenient;
+        self.disassembly = self
+            .cpu
+            .memory()
+            .iter_instructions(MemoryAddress::from_u16(0))
+            .map(|(address, raw_instruction)| {
+                (
+                    address,
+                    raw_instruction,
+                    if lenient {
+                        Ok(Instruction::from_u16_lenient(raw_instruction))
+                    } else {
+                        Instruction::try_from_u16(raw_instruction)
+                    },
+                )
+            })
+            .collect();
+        self.disassembly_dirty = false;
     }
 }
 
 #[macroquad::main(window_conf)]
 async fn main() {
-    let roms = vec![
-        "./roms/ibm-logo.ch8",
-        "./roms/SCTEST.ch8",
-        "./roms/bc_test.ch8",
-        "./roms/delay_timer_test.ch8",
-        "./roms/test_suite/1-chip8-logo.ch8",
-        "./roms/test_suite/2-ibm-logo.ch8",
-        "./roms/test_suite/3-corax+.ch8",
-        "./roms/test_suite/4-flags.ch8",
-        "./roms/test_suite/5-quirks.ch8",
-        "./roms/test_suite/6-keypad.ch8",
-        "./roms/test_suite/7-beep.ch8",
-        "./roms/test_suite/8-scrolling.ch8",
-    ];
-    let mut state = UiState::default();
+    let (args, config_path, config) = match load_startup_config() {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut state = UiState {
+        ticks_per_frame: ticks_per_frame_for_speed(config.cpu_speed),
+        fullscreen: config.fullscreen,
+        config,
+        config_path,
+        ..Default::default()
+    };
+    state.refresh_roms();
+    if let Some(rom) = &args.rom {
+        state.load_rom(rom);
+    }
 
+    // egui only knows about focused text fields after drawing, so the shortcuts use the last frame
+    let mut egui_wants_keyboard = false;
     loop {
         clear_background(RED);
 
-        let instructions_per_frame = INSTRUCTION_RATE / 60;
-        for _ in 0..instructions_per_frame {
-            if state.is_running() {
-                state.handle_tick();
-            }
+        if !egui_wants_keyboard {
+            handle_shortcuts(&mut state);
         }
+        state.run_frame();
 
-        draw_screen(&state.cpu.display);
+        let layout = ScreenLayout::for_window(screen_width(), screen_height());
+        draw_screen(state.cpu.display(), &state.config, &layout);
 
         egui_macroquad::ui(|egui_ctx| {
+            egui_wants_keyboard = egui_ctx.wants_keyboard_input();
             egui::SidePanel::right("Instructions")
-                .exact_width(400.0)
+                .exact_width(layout.side_panel_width)
                 .resizable(false)
                 .show(egui_ctx, |ui| {
                     ui.separator();
@@ -233,25 +881,33 @@ async fn main() {
                     ui.separator();
                     draw_instructions(ui, &mut state);
                     ui.separator();
-                    draw_register_grid(ui, &state);
+                    draw_register_grid(ui, &mut state);
+                    ui.separator();
+                    draw_stack(ui, &mut state);
                     ui.separator();
-                    draw_stack(ui, &state);
+                    draw_changes(ui, &state);
+                    ui.separator();
+                    draw_trace(ui, &mut state);
                 });
 
             egui::SidePanel::left("Roms")
-                .exact_width(400.0)
+                .exact_width(layout.side_panel_width)
                 .resizable(false)
                 .show(egui_ctx, |ui| {
                     ui.separator();
-                    draw_roms(ui, &mut state, &roms);
+                    draw_roms(ui, &mut state);
+                    ui.separator();
+                    draw_settings(ui, &mut state);
                     ui.separator();
                     draw_break_point_list(ui, &mut state);
                     ui.separator();
+                    draw_stats(ui, &mut state);
+                    ui.separator();
                     draw_output(ui, &mut state);
                 });
 
             egui::TopBottomPanel::bottom("Memory")
-                .exact_height(400.0)
+                .exact_height(layout.memory_panel_height)
                 .show(egui_ctx, |ui| {
                     draw_memory_grid(ui, &mut state);
                 });
@@ -263,27 +919,126 @@ async fn main() {
     }
 }
 
-fn draw_memory_grid(ui: &mut egui::Ui, state: &mut UiState) {
-    let step = 16;
-    let bytes = state
-        .cpu
-        .memory
-        .read_slice(MemoryAddress::from_u16(0), MEMORY_SIZE)
-        .unwrap();
-    let rows_of_bytes = bytes.chunks(16);
-    let mut byte_search = Vec::new();
-
-    let parse_result = handle_byte_search_conversion(&state.memory_filter);
-    let text_color = match parse_result {
-        Ok(_) => None,
-        Err(_) => Some(egui::Color32::RED),
-    };
+const SHORTCUTS: [(&str, &str); 5] = [
+    ("Space", "Pause / Continue"),
+    ("Escape", "Pause"),
+    ("F5", "Step"),
+    ("F2", "Restart"),
+    ("F11", "Toggle Fullscreen"),
+];
+
+fn handle_shortcuts(state: &mut UiState) {
+    if is_key_pressed(KeyCode::Space) {
+        state.toggle_execution();
+    }
+    if is_key_pressed(KeyCode::Escape) && state.is_running() {
+        state.execution = CpuExecution::Paused;
+    }
+    if is_key_pressed(KeyCode::F5) && state.is_paused() {
+        state.handle_tick();
+    }
+    if is_key_pressed(KeyCode::F2) && state.can_restart() {
+        state.restart();
+    }
+    if is_key_pressed(KeyCode::F11) {
+        state.fullscreen = !state.fullscreen;
+        set_fullscreen(state.fullscreen);
+    }
+}
+
+/// Parses the command line arguments and loads the config they point to.
+/// Arguments given on the command line take precedence over the config file.
+fn load_startup_config() -> Result<(Args, String, EmulatorConfig)> {
+    let args = Args::from_env()?;
+    let config_path = args
+        .config
+        .clone()
+        .unwrap_or_else(|| CONFIG_PATH.to_string());
+
+    let mut config = EmulatorConfig::load(&config_path)?;
+    if let Some(speed) = args.speed {
+        config.cpu_speed = speed;
+    }
+    if let Some(mode) = args.mode {
+        config.compatibility_mode = mode;
+    }
+
+    Ok((args, config_path, config))
+}
+
+/// Shortens paths inside the working directory to a path relative to it
+fn relative_path(path: &str) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|current_dir| {
+            Path::new(path)
+                .strip_prefix(current_dir)
+                .ok()
+                .map(|relative| relative.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn find_roms(directory: &Path) -> Result<Vec<String>> {
+    let mut roms = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            roms.extend(find_roms(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "ch8") {
+            roms.push(path.to_string_lossy().to_string());
+        }
+    }
+
+    Ok(roms)
+}
 
-    if parse_result.is_ok() {
-        byte_search = parse_result.unwrap();
+/// Background color of a byte in the memory grid.
+/// The two bytes of the current instruction take precedence over the I register.
+fn memory_byte_color(
+    address: MemoryAddress,
+    program_counter: MemoryAddress,
+    index: MemoryAddress,
+    is_search_match: bool,
+    is_changed: bool,
+) -> egui::Color32 {
+    let is_current_instruction = address == program_counter || address == program_counter.add(1);
+    match (is_current_instruction, is_search_match) {
+        (true, true) => PC_AND_MATCH_COLOR,
+        (true, false) => egui::Color32::LIGHT_BLUE,
+        (false, true) => egui::Color32::YELLOW,
+        (false, false) if is_changed => CHANGED_MEMORY_COLOR,
+        (false, false) if address == index => egui::Color32::LIGHT_GREEN,
+        (false, false) => egui::Color32::TRANSPARENT,
     }
+}
+
+fn draw_memory_grid(ui: &mut egui::Ui, state: &mut UiState) {
+    let step = 16;
+    state.refresh_memory_snapshot();
+    let memory = state.memory_snapshot.as_ref();
 
-    let byte_indexes_to_highlight = compute_byte_indexes_to_highlight(&byte_search, bytes);
+    let text_color = match state.memory_matches {
+        Some(_) => None,
+        None => Some(egui::Color32::RED),
+    };
+    let empty_matches = HashSet::new();
+    let byte_indexes_to_highlight = state
+        .memory_matches
+        .as_ref()
+        .map_or(&empty_matches, |(_, matches)| matches);
+    let program_counter = state.cpu.pc_address();
+    let index = state.cpu.index_address();
+    let changed_addresses = state
+        .last_diff
+        .as_ref()
+        .map(|diff| {
+            diff.changed_memory
+                .iter()
+                .map(|(address, _, _)| *address)
+                .collect::<HashSet<_>>()
+        })
+        .unwrap_or_default();
 
     ui.separator();
     ui.horizontal(|ui| {
@@ -292,6 +1047,23 @@ fn draw_memory_grid(ui: &mut egui::Ui, state: &mut UiState) {
             .text_color_opt(text_color);
         ui.label("Search:");
         ui.add(text_edit);
+        if state.memory_matches.is_some() && !state.memory_filter.is_empty() {
+            match state.memory_match_count {
+                0 => ui.label("No matches found"),
+                1 => ui.label("Found 1 occurrence"),
+                count => ui.label(format!("Found {} occurrences", count)),
+            };
+        }
+        ui.separator();
+        for (label, color) in [
+            ("PC", egui::Color32::LIGHT_BLUE),
+            ("I", egui::Color32::LIGHT_GREEN),
+            ("Match", egui::Color32::YELLOW),
+            ("PC + Match", PC_AND_MATCH_COLOR),
+            ("Changed", CHANGED_MEMORY_COLOR),
+        ] {
+            ui.label(egui::RichText::new(label).background_color(color));
+        }
     });
 
     ui.separator();
@@ -320,16 +1092,21 @@ fn draw_memory_grid(ui: &mut egui::Ui, state: &mut UiState) {
                 .min_col_width(0.)
                 .striped(true)
                 .show(ui, |ui| {
-                    for (row_idx, bytes) in rows_of_bytes.enumerate() {
-                        ui.monospace(format!("0x{:0>4X}", row_idx * step));
-                        for (col_idx, b) in bytes.iter().enumerate() {
-                            let bg_color = if byte_indexes_to_highlight
-                                .contains(&(row_idx * step + col_idx))
-                            {
-                                egui::Color32::YELLOW
-                            } else {
-                                egui::Color32::TRANSPARENT
-                            };
+                    let Some(memory) = memory else {
+                        return;
+                    };
+                    let row_starts = (0..MEMORY_SIZE as u16).step_by(step);
+                    for row_start in row_starts.map(MemoryAddress::from_u16) {
+                        let row = || memory.iter_data(row_start).take(step);
+                        ui.monospace(format!("0x{:0>4X}", *row_start));
+                        for (col_idx, (address, b)) in row().enumerate() {
+                            let bg_color = memory_byte_color(
+                                address,
+                                program_counter,
+                                index,
+                                byte_indexes_to_highlight.contains(&address),
+                                changed_addresses.contains(&address),
+                            );
                             let text = egui::RichText::new(format!("{:0>2X}", b))
                                 .monospace()
                                 .background_color(bg_color);
@@ -339,7 +1116,7 @@ fn draw_memory_grid(ui: &mut egui::Ui, state: &mut UiState) {
                                 ui.label("");
                             }
                         }
-                        let txt = bytes.iter().map(|b| byte_to_char(*b)).collect::<String>();
+                        let txt = row().map(|(_, b)| byte_to_char(b)).collect::<String>();
                         ui.monospace(txt);
                         ui.end_row()
                     }
@@ -348,96 +1125,86 @@ fn draw_memory_grid(ui: &mut egui::Ui, state: &mut UiState) {
     ui.separator();
 }
 
-fn compute_byte_indexes_to_highlight(expanded_search: &[Vec<u8>], bytes: &[u8]) -> HashSet<usize> {
-    if expanded_search.len() == 0 {
-        HashSet::new()
-    } else {
-        let window_len = expanded_search[0].len();
-        expanded_search
-            .into_iter()
-            .flat_map(|search| {
-                bytes
-                    .windows(window_len)
-                    .enumerate()
-                    .filter(|(_, window)| *window == search)
-                    .flat_map(|(idx, _)| idx..(idx + window_len))
-                    .collect::<HashSet<_>>()
-            })
-            .collect::<HashSet<_>>()
+/// A byte of a memory search. Only the nibbles selected by `mask` have to match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SearchByte {
+    value: u8,
+    mask: u8,
+}
+
+impl SearchByte {
+    fn matches(&self, byte: u8) -> bool {
+        byte & self.mask == self.value
     }
 }
 
-fn handle_byte_search_conversion(input: &str) -> Result<Vec<Vec<u8>>> {
-    let expanded_values = expand_byte_search(input);
+/// Marks every byte covered by a search match starting at one of `starts`
+fn compute_byte_indexes_to_highlight(
+    starts: &[MemoryAddress],
+    search_length: usize,
+) -> HashSet<MemoryAddress> {
+    starts
+        .iter()
+        .flat_map(|address| (0..search_length as u16).map(move |offset| address.add(offset)))
+        .collect()
+}
 
-    let value = expanded_values
+/// Finds the start addresses of the search. Bytes with a wildcard nibble are searched as wildcard
+/// bytes and checked against their known nibble afterwards.
+fn find_byte_search(search: &[SearchByte], memory: &Memory) -> Vec<MemoryAddress> {
+    let partial_bytes = search
         .iter()
-        .map(|v| parse_byte_search(v))
-        .collect::<Result<Vec<_>>>()?
-        .into_iter()
+        .enumerate()
+        .filter(|(_, byte)| byte.mask != 0x00 && byte.mask != 0xFF)
         .collect::<Vec<_>>();
 
-    Ok(value)
+    memory
+        .find_pattern(&expand_byte_search(search))
+        .into_iter()
+        .filter(|address| {
+            partial_bytes
+                .iter()
+                .all(|(offset, byte)| byte.matches(memory[address.add(*offset as u16)]))
+        })
+        .collect()
 }
 
-fn parse_byte_search(value: &str) -> Result<Vec<u8>> {
-    if value == "" {
-        return Ok(vec![]);
-    }
-
-    if value.chars().any(|c| c != '?' && !c.is_ascii_hexdigit()) {
-        return Err(anyhow!("Invalid hex character"));
-    }
-
-    let value = if value.len() % 2 != 0 {
-        format! {"0{}", value}
+fn handle_byte_search_conversion(input: &str) -> Result<Vec<SearchByte>> {
+    let value = if !input.len().is_multiple_of(2) {
+        format! {"0{}", input}
     } else {
-        value.to_string()
+        input.to_string()
     };
 
-    let reversed = value
+    let nibbles = value
         .chars()
-        .rev()
-        .collect::<Vec<_>>()
-        .chunks(2)
-        .map(|chars| {
-            u8::from_str_radix(format!("{}{}", chars[1], chars[0]).as_str(), 16).with_context(|| "")
+        .map(|c| match c {
+            '?' => Ok((0, 0x0)),
+            c => c
+                .to_digit(16)
+                .map(|digit| (digit as u8, 0xF))
+                .ok_or_else(|| anyhow!("Invalid hex character in '{}'", input)),
         })
-        .rev()
-        .collect::<Result<Vec<u8>>>()?;
-
-    let mut single_char_value = value
-        .chars()
-        .rev()
-        .take(value.len() % 2)
-        .map(|c| u8::from_str_radix(&c.to_string(), 16).with_context(|| ""))
         .collect::<Result<Vec<_>>>()?;
 
-    single_char_value.extend(reversed);
-    return Ok(single_char_value);
-}
-
-fn expand_byte_search(value: &str) -> Vec<String> {
-    if value == "" {
-        return vec![];
-    }
-    if !value.contains('?') {
-        return vec![value.to_string()];
-    }
+    let bytes = nibbles
+        .chunks(2)
+        .map(|pair| SearchByte {
+            value: pair[0].0 << 4 | pair[1].0,
+            mask: pair[0].1 << 4 | pair[1].1,
+        })
+        .collect();
 
-    let mut result = vec![value.to_string()];
-    for _ in value.chars().filter(|c| *c == '?') {
-        let mut temp = Vec::new();
-        for value in result.iter() {
-            for x in 0..16_u8 {
-                let replaced = value.replacen('?', format!("{:X}", x).as_str(), 1);
-                temp.push(replaced);
-            }
-        }
-        result = temp;
-    }
+    Ok(bytes)
+}
 
-    result
+/// Turns the search into a pattern for `Memory::find_pattern`. Bytes containing a wildcard become
+/// wildcard bytes.
+fn expand_byte_search(search: &[SearchByte]) -> Vec<Option<u8>> {
+    search
+        .iter()
+        .map(|byte| (byte.mask == 0xFF).then_some(byte.value))
+        .collect()
 }
 
 fn byte_to_char(byte: u8) -> char {
@@ -452,39 +1219,170 @@ fn byte_to_char(byte: u8) -> char {
     '.'
 }
 
-fn draw_register_grid(ui: &mut egui::Ui, state: &UiState) {
+fn parse_address(input: &str) -> Result<u16> {
+    let value =
+        u16::from_str_radix(input, 16).with_context(|| format!("Invalid address '{}'", input))?;
+    if !Memory::is_address_valid(MemoryAddress::from_u16(value)) {
+        return Err(anyhow!("Address 0x{:0>4X} exceeds the memory", value));
+    }
+
+    Ok(value)
+}
+
+fn format_timer(timer: &Timer) -> String {
+    let remaining_ticks = timer.remaining_ticks();
+    if remaining_ticks == 0 {
+        return format!("{:0>4X}", timer.get());
+    }
+
+    format!(
+        "{:0>4X} (decrement in {} ticks)",
+        timer.get(),
+        remaining_ticks
+    )
+}
+
+fn draw_register_grid(ui: &mut egui::Ui, state: &mut UiState) {
     ui.heading("Registers");
     egui::Grid::new("registers")
         .num_columns(4)
         .spacing([40.0, 4.0])
         .striped(true)
         .show(ui, |ui| draw_register_grid_content(ui, state));
-}
 
-fn draw_stack(ui: &mut egui::Ui, state: &UiState) {
-    ui.heading("Stack");
+    let rpl_flags = state
+        .cpu
+        .rpl_flags
+        .iter()
+        .map(|flag| format!("{:0>2X}", flag))
+        .collect::<Vec<_>>()
+        .join(" ");
+    ui.horizontal(|ui| {
+        ui.label("RPL:");
+        ui.monospace(rpl_flags);
+    });
+}
+
+fn draw_stack(ui: &mut egui::Ui, state: &mut UiState) {
+    ui.heading("Stack");
+    let mut jump_target = None;
     egui::Grid::new("stack")
         .num_columns(2)
         .spacing([40.0, 4.0])
         .striped(true)
         .show(ui, |ui| {
-            for (index, address) in state.cpu.stack.iter().enumerate() {
-                ui.label(format!("{:>2}", index));
-                ui.label(format!("{:0>4}", **address));
+            for (depth, address, instruction) in state.cpu.stack_trace() {
+                ui.label(format!("{:>2}", depth));
+                let text = match instruction {
+                    Ok(instruction) => {
+                        format!("Called from {} at 0x{:0>3X}", instruction, *address - 2)
+                    }
+                    Err(_) => format!("Returns to 0x{:0>3X}", *address),
+                };
+                let label = egui::Label::new(text).sense(egui::Sense::click());
+                if ui
+                    .add(label)
+                    .on_hover_text("Double click to show in the instructions")
+                    .double_clicked()
+                {
+                    jump_target = Some(*address);
+                }
                 ui.end_row();
             }
         });
+
+    if jump_target.is_some() {
+        state.scroll_target = jump_target;
+    }
+}
+
+fn draw_changes(ui: &mut egui::Ui, state: &UiState) {
+    ui.heading("Changes");
+    let Some(diff) = &state.last_diff else {
+        ui.label("Step to see the changes of an instruction");
+        return;
+    };
+
+    for line in diff.describe() {
+        ui.monospace(line);
+    }
+}
+
+fn draw_trace(ui: &mut egui::Ui, state: &mut UiState) {
+    ui.heading("Trace");
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut state.trace_path);
+        if ui.button("Export Trace").clicked() {
+            let result = state
+                .export_trace(&state.trace_path)
+                .with_context(|| format!("Failed exporting trace to '{}'", state.trace_path));
+            match result {
+                Ok(()) => state.push_output(format!(
+                    "Exported {} trace entries to '{}'",
+                    state.trace.len(),
+                    state.trace_path
+                )),
+                Err(err) => state.push_output(format!("{:?}", err)),
+            }
+        }
+    });
+
+    let skipped = state.trace.len().saturating_sub(VISIBLE_TRACE_ENTRIES);
+    for entry in state.trace.iter().skip(skipped) {
+        ui.monospace(format!(
+            "{:#06X} {:04X} {}",
+            *entry.address,
+            entry.raw_instruction,
+            entry.mnemonic()
+        ));
+    }
+}
+
+fn write_trace_csv<'a>(
+    writer: &mut impl Write,
+    entries: impl IntoIterator<Item = &'a TraceEntry>,
+) -> Result<()> {
+    write!(writer, "address,raw_opcode,mnemonic")?;
+    for register in 0..16 {
+        write!(writer, ",V{:X}", register)?;
+    }
+    writeln!(writer)?;
+
+    for entry in entries {
+        write!(
+            writer,
+            "{:#06X},{:#06X},\"{}\"",
+            *entry.address,
+            entry.raw_instruction,
+            entry.mnemonic()
+        )?;
+        for value in entry.registers {
+            write!(writer, ",{:#04X}", value)?;
+        }
+        writeln!(writer)?;
+    }
+
+    Ok(())
 }
 
 fn draw_degubbing_controlls(ui: &mut egui::Ui, state: &mut UiState) {
     ui.horizontal(|ui| {
+        ui.add_enabled_ui(state.can_step_back(), |ui| {
+            if ui.button("Step Back").clicked() {
+                state.step_back();
+            }
+        });
+
         ui.add_enabled_ui(state.is_paused(), |ui| {
             if ui.button("Step").clicked() {
                 state.handle_tick();
             }
         });
 
-        ui.add_enabled_ui(!state.has_failed, |ui| match state.execution {
+        match state.execution {
+            CpuExecution::Unloaded => {
+                ui.add_enabled(false, egui::Button::new("Continue"));
+            }
             CpuExecution::Paused => {
                 if ui.button("Continue").clicked() {
                     state.execution = CpuExecution::Running;
@@ -495,43 +1393,272 @@ fn draw_degubbing_controlls(ui: &mut egui::Ui, state: &mut UiState) {
                     state.execution = CpuExecution::Paused;
                 }
             }
-        });
+            CpuExecution::Crashed => {
+                ui.label(egui::RichText::new("Crashed").color(egui::Color32::RED));
+            }
+            CpuExecution::Exited => {
+                ui.label("Exited");
+            }
+        }
 
         ui.add_enabled_ui(state.can_restart(), |ui| {
             if ui.button("Restart").clicked() {
                 state.restart();
             }
         });
+
+        ui.add_enabled_ui(state.is_paused(), |ui| {
+            if ui.button("Clear Screen").clicked() {
+                state.cpu.display_mut().clear();
+            }
+        });
+    });
+
+    ui.horizontal(|ui| {
+        let seed = format!("{:0>16X}", state.cpu.seed());
+        ui.label("Seed:");
+        ui.monospace(&seed);
+        if ui.button("Copy").clicked() {
+            ui.output_mut(|o| o.copied_text = seed);
+        }
+        ui.checkbox(&mut state.replay_seed, "Replay on restart");
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Ticks:");
+        ui.monospace(state.cpu.tick_count().to_string());
+        if let Some(instruction) = state.cpu.last_instruction() {
+            ui.label("Last:");
+            ui.monospace(instruction.to_string());
+        }
+        ui.label("Lit pixels:");
+        ui.monospace(state.cpu.display().count_lit_pixels().to_string());
+    });
+
+    ui.horizontal(|ui| {
+        ui.add_enabled_ui(state.is_running(), |ui| {
+            ui.add(
+                egui::Slider::new(&mut state.ticks_per_frame, 1..=500).text("Speed (ticks/frame)"),
+            );
+        });
+        ui.label(format!("~{} Hz", state.ticks_per_frame * FRAME_RATE));
+    });
+
+    ui.collapsing("Shortcuts", |ui| {
+        egui::Grid::new("shortcuts").num_columns(2).show(ui, |ui| {
+            for (key, action) in SHORTCUTS {
+                ui.monospace(key);
+                ui.label(action);
+                ui.end_row();
+            }
+        });
     });
 }
 
-fn draw_roms(ui: &mut egui::Ui, state: &mut UiState, roms: &[&str]) {
+fn ticks_per_frame_for_speed(cpu_speed: u64) -> u32 {
+    (cpu_speed / FRAME_RATE as u64) as u32
+}
+
+fn draw_roms(ui: &mut egui::Ui, state: &mut UiState) {
+    let mut rom_to_load = None;
+    if !state.config.recent_roms.is_empty() {
+        ui.heading("Recent ROMs");
+        for rom in state.config.recent_roms.iter() {
+            if ui.button(relative_path(rom)).clicked() {
+                rom_to_load = Some(rom.clone());
+            }
+        }
+        ui.separator();
+    }
+
     ui.heading("Roms");
-    for rom in roms {
-        if ui.button(*rom).clicked() {
-            state.load_rom(rom);
+    for rom in state.roms.iter() {
+        let label = rom.name.as_ref().unwrap_or(&rom.path);
+        if ui.button(label).clicked() {
+            rom_to_load = Some(rom.path.clone());
         };
     }
+
+    if let Some(rom) = rom_to_load {
+        state.load_rom(&rom);
+    }
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.label("Font:");
+        ui.add(egui::TextEdit::singleline(&mut state.font_path).desired_width(120.0));
+        if ui.button("Load Font").clicked() {
+            if let Err(err) = state.load_font() {
+                state.push_output(format!("{:?}", err));
+            }
+        }
+    });
+
+    if state.can_restart() {
+        ui.label(format!(
+            "Loaded: {} (CRC32 {})",
+            state.current_rom,
+            format_checksum(state.cpu.rom_checksum())
+        ));
+    }
+
+    if ui.button("Save Screenshot").clicked() {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let path = format!("screenshot_{}.png", timestamp);
+        match save_screenshot(state.cpu.display(), &state.config, &path) {
+            Ok(()) => state.push_output(format!("Saved screenshot to '{}'", path)),
+            Err(err) => state.push_output(format!("{:?}", err)),
+        }
+    }
+}
+
+fn draw_settings(ui: &mut egui::Ui, state: &mut UiState) {
+    ui.heading("Settings");
+    let mut has_changed = false;
+    egui::Grid::new("settings")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Rom directory:");
+            if ui
+                .text_edit_singleline(&mut state.config.rom_directory)
+                .lost_focus()
+            {
+                state.refresh_roms();
+                has_changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Speed:");
+            let speed = egui::DragValue::new(&mut state.config.cpu_speed)
+                .clamp_range(60..=10000)
+                .suffix(" Hz");
+            if ui.add(speed).changed() {
+                state.cpu.set_cpu_rate(state.config.cpu_speed);
+                state.ticks_per_frame = ticks_per_frame_for_speed(state.config.cpu_speed);
+                has_changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Mode:");
+            egui::ComboBox::from_id_source("compatibility_mode")
+                .selected_text(format!("{:?}", state.config.compatibility_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        CompatibilityMode::Chip8,
+                        CompatibilityMode::Chip48,
+                        CompatibilityMode::SuperChip,
+                    ] {
+                        let label = format!("{:?}", mode);
+                        if ui
+                            .selectable_value(&mut state.config.compatibility_mode, mode, label)
+                            .changed()
+                        {
+                            let mut cpu_config = *state.cpu.config();
+                            cpu_config.set_compatibility_mode(mode);
+                            state.cpu.set_config(cpu_config);
+                            has_changed = true;
+                        }
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Lenient:");
+            if ui
+                .checkbox(&mut state.config.lenient, "Skip invalid instructions")
+                .changed()
+            {
+                let mut cpu_config = *state.cpu.config();
+                cpu_config.lenient = state.config.lenient;
+                state.cpu.set_config(cpu_config);
+                state.disassembly_dirty = true;
+                has_changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Foreground:");
+            if ui
+                .color_edit_button_srgb(&mut state.config.foreground_color)
+                .changed()
+            {
+                has_changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Background:");
+            if ui
+                .color_edit_button_srgb(&mut state.config.background_color)
+                .changed()
+            {
+                has_changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Plane 2:");
+            if ui
+                .color_edit_button_srgb(&mut state.config.plane2_color)
+                .changed()
+            {
+                has_changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Both planes:");
+            if ui
+                .color_edit_button_srgb(&mut state.config.both_planes_color)
+                .changed()
+            {
+                has_changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Key mapping:");
+            if ui
+                .text_edit_singleline(&mut state.config.key_mapping)
+                .lost_focus()
+            {
+                has_changed = true;
+            }
+            ui.end_row();
+        });
+
+    if has_changed {
+        state.save_config();
+    }
 }
 
 fn draw_instructions(ui: &mut egui::Ui, state: &mut UiState) {
-    let start = MemoryAddress::from_u16(0);
-    let instructions = state
-        .cpu
-        .memory
-        .read_slice(start, MEMORY_SIZE)
-        .unwrap()
-        .chunks(2)
-        .map(|c| join_bytes(c[0], c[1]))
-        .collect::<Vec<_>>();
+    if state.disassembly_dirty {
+        state.refresh_disassembly();
+    }
 
-    let start = usize::from(start);
     ui.heading("Instructions");
+    ui.horizontal(|ui| {
+        let is_valid = parse_address(state.goto_address.trim()).is_ok();
+        let text_color =
+            (!is_valid && !state.goto_address.is_empty()).then_some(egui::Color32::RED);
+        let text_edit = egui::TextEdit::singleline(&mut state.goto_address)
+            .desired_width(60.0)
+            .text_color_opt(text_color);
+        ui.label("Go to:");
+        ui.add(text_edit);
+        ui.add_enabled_ui(is_valid, |ui| {
+            if ui.button("Jump").clicked() {
+                if let Err(err) = state.apply_goto_address() {
+                    state.push_output(format!("{:?}", err));
+                }
+            }
+        });
+    });
+
     let text_height = egui::TextStyle::Body
         .resolve(ui.style())
         .size
         .max(ui.spacing().interact_size.y);
-    let total_rows = instructions.len();
+    let total_rows = state.disassembly.len();
 
     let mut table = TableBuilder::new(ui)
         .striped(true)
@@ -543,12 +1670,12 @@ fn draw_instructions(ui: &mut egui::Ui, state: &mut UiState) {
         .column(Column::exact(60.))
         .column(Column::remainder());
 
-    if state.has_ticked {
-        table = table.scroll_to_row(
-            *state.cpu.program_counter as usize / 2,
-            Some(egui::Align::TOP),
-        );
-        state.has_ticked = false;
+    let program_counter = state.cpu.pc();
+    if let Some(address) = state.scroll_target.take() {
+        table = table.scroll_to_row(address as usize / 2, Some(egui::Align::TOP));
+    } else if state.last_drawn_pc != Some(program_counter) {
+        table = table.scroll_to_row(program_counter as usize / 2, Some(egui::Align::TOP));
+        state.last_drawn_pc = Some(program_counter);
     }
 
     table
@@ -571,13 +1698,10 @@ fn draw_instructions(ui: &mut egui::Ui, state: &mut UiState) {
         })
         .body(|body| {
             body.rows(text_height, total_rows, |row_index, mut row| {
-                let raw_instruction = instructions[row_index];
-                let current_address = start + 2 * row_index;
+                let (address, raw_instruction, instruction) = &state.disassembly[row_index];
+                let current_address = usize::from(*address);
                 row.col(|ui| {
-                    if state
-                        .breakpoint_addresses
-                        .contains(&(current_address as u16))
-                    {
+                    if state.breakpoints.contains(&Breakpoint::AtAddress(*address)) {
                         ui.label(
                             egui::RichText::new(">")
                                 .monospace()
@@ -588,7 +1712,7 @@ fn draw_instructions(ui: &mut egui::Ui, state: &mut UiState) {
                     }
                 });
                 row.col(|ui| {
-                    if current_address == usize::from(state.cpu.program_counter) {
+                    if current_address == usize::from(state.cpu.pc_address()) {
                         ui.monospace("=>");
                     } else {
                         ui.monospace("");
@@ -602,8 +1726,21 @@ fn draw_instructions(ui: &mut egui::Ui, state: &mut UiState) {
                     ui.monospace(format!("0x{:0>4X}", raw_instruction));
                 });
                 row.col(|ui| {
-                    if let Ok(instruction) = Instruction::try_from_u16(raw_instruction) {
-                        ui.monospace(format!("{}", instruction));
+                    if let Ok(instruction) = instruction {
+                        ui.label(
+                            egui::RichText::new(" ")
+                                .monospace()
+                                .background_color(category_color(instruction.category())),
+                        );
+                        if let Instruction::Nop(_) = instruction {
+                            ui.label(
+                                egui::RichText::new(format!("{}", instruction))
+                                    .monospace()
+                                    .color(egui::Color32::DARK_GRAY),
+                            );
+                        } else {
+                            ui.monospace(format!("{}", instruction));
+                        }
                     } else {
                         ui.monospace("???");
                     }
@@ -612,125 +1749,148 @@ fn draw_instructions(ui: &mut egui::Ui, state: &mut UiState) {
         });
 }
 
-fn draw_register_grid_content(ui: &mut egui::Ui, state: &UiState) {
-    let cpu = &state.cpu;
+fn category_color(category: InstructionCategory) -> egui::Color32 {
+    match category {
+        InstructionCategory::Arithmetic => egui::Color32::LIGHT_BLUE,
+        InstructionCategory::Bitwise => egui::Color32::LIGHT_GREEN,
+        InstructionCategory::Memory => egui::Color32::YELLOW,
+        InstructionCategory::Display => egui::Color32::LIGHT_RED,
+        InstructionCategory::ControlFlow => egui::Color32::GOLD,
+        InstructionCategory::IO => egui::Color32::KHAKI,
+        InstructionCategory::Timer => egui::Color32::GRAY,
+        InstructionCategory::Padding => egui::Color32::DARK_GRAY,
+    }
+}
+
+fn draw_register_grid_content(ui: &mut egui::Ui, state: &mut UiState) {
     ui.label("PC:");
-    ui.label(format!("{:0>4X}", *cpu.program_counter));
+    draw_editable_register(ui, state, EditableRegister::ProgramCounter);
 
     ui.label("I:");
-    ui.label(format!("{:0>4X}", *cpu.index));
-
-    ui.end_row();
-
-    ui.label("V0:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(0))));
-
-    ui.label("V1:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(1))));
-
-    ui.end_row();
-
-    ui.label("V2:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(2))));
-
-    ui.label("V3:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(3))));
-
-    ui.end_row();
-
-    ui.label("V4:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(4))));
-
-    ui.label("V5:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(5))));
-
-    ui.end_row();
-
-    ui.label("V6:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(6))));
-
-    ui.label("V7:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(7))));
+    draw_editable_register(ui, state, EditableRegister::Index);
 
     ui.end_row();
 
     ui.label("DT:");
-    ui.label(format!("{:0>4X}", cpu.delay_timer.get()));
+    ui.label(format_timer(&state.cpu.delay_timer));
 
     ui.label("ST:");
-    ui.label(format!("{:0>4X}", cpu.sound_timer));
-
-    ui.end_row();
-
-    ui.label("V8:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(8))));
-
-    ui.label("V9:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(9))));
-
-    ui.end_row();
-
-    ui.label("VA:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(10))));
-
-    ui.label("VB:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(11))));
+    ui.label(format_timer(&state.cpu.sound_timer));
 
     ui.end_row();
 
-    ui.label("VC:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(12))));
-
-    ui.label("VD:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(13))));
+    let registers = (0..16).map(U4::new).collect::<Vec<_>>();
+    for row in registers.chunks(2) {
+        for register in row {
+            ui.label(format!("V{:X}:", **register));
+            draw_editable_register(ui, state, EditableRegister::Variable(**register));
+        }
+        ui.end_row();
+    }
+}
 
-    ui.end_row();
+fn draw_editable_register(ui: &mut egui::Ui, state: &mut UiState, register: EditableRegister) {
+    ui.horizontal(|ui| {
+        match state.register_edit.as_mut() {
+            Some(edit) if edit.register == register => {
+                let response =
+                    ui.add(egui::TextEdit::singleline(&mut edit.input).desired_width(40.0));
+                if response.lost_focus() {
+                    let input = edit.input.clone();
+                    state.register_edit = None;
+                    if let Err(err) = state.apply_register_edit(register, &input) {
+                        state.push_output(format!("{:?}", err));
+                    }
+                } else if !response.has_focus() {
+                    response.request_focus();
+                }
+            }
+            _ => {
+                let value = format!("{:0>4X}", state.register_value(register));
+                let label = egui::Label::new(value).sense(egui::Sense::click());
+                if ui.add_enabled(state.is_paused(), label).clicked() {
+                    state.register_edit = Some(RegisterEdit {
+                        register,
+                        input: format!("{:X}", state.register_value(register)),
+                    });
+                }
+            }
+        }
 
-    ui.label("VE:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(14))));
+        if state.modified_registers.contains(&register) {
+            ui.colored_label(egui::Color32::RED, "*");
+        }
+    });
+}
 
-    ui.label("VF:");
-    ui.label(format!("{:0>4X}", cpu.registers.get_value(U4::new(15))));
+fn draw_stats(ui: &mut egui::Ui, state: &mut UiState) {
+    ui.heading("Statistics");
+    let now = Instant::now();
+    state.stats.update(now);
+    ui.label(state.stats.summary(now));
 }
 
 fn draw_output(ui: &mut egui::Ui, state: &UiState) {
     ui.heading("Output");
+    if state.hidden_output_lines > 0 {
+        ui.label(format!(
+            "…{} older messages hidden…",
+            state.hidden_output_lines
+        ));
+    }
     for line in state.output.iter() {
         ui.label(line);
     }
 }
 
-fn draw_screen(display: &Display) {
-    const PIXEL_SIZE: f32 = 16.;
-    const X_OFFSET: f32 = 448.;
-    const Y_OFFSET: f32 = 84.;
-
-    // draw_line(0., 0., 64);
-    for (row_index, row) in display.pixels.iter().enumerate() {
-        let mut pixel_mask = 1 << 63;
-        let mut column_index = 0;
+fn draw_screen(display: &Display, config: &EmulatorConfig, layout: &ScreenLayout) {
+    let colors = config
+        .display_colors()
+        .map(|[r, g, b]| Color::from_rgba(r, g, b, 255));
 
-        while pixel_mask > 0 {
-            let x_pos = column_index as f32 * PIXEL_SIZE + X_OFFSET;
-            let y_pos = row_index as f32 * PIXEL_SIZE + Y_OFFSET;
+    // the high resolution mode uses smaller pixels to keep the screen size
+    let (width, height) = display.resolution();
+    let pixel_size = layout.pixel_size * 64. / width as f32;
 
-            if (row & pixel_mask) > 0 {
-                draw_rectangle(x_pos, y_pos, PIXEL_SIZE, PIXEL_SIZE, WHITE);
-            } else {
-                draw_rectangle(x_pos, y_pos, PIXEL_SIZE, PIXEL_SIZE, BLACK);
-            }
+    for row_index in 0..height {
+        for column_index in 0..width {
+            let x_pos = column_index as f32 * pixel_size + layout.display_x;
+            let y_pos = row_index as f32 * pixel_size + layout.display_y;
 
-            column_index += 1;
-            pixel_mask = pixel_mask >> 1;
+            let color = colors[display.pixel_color(column_index, row_index) as usize];
+            draw_rectangle(x_pos, y_pos, pixel_size, pixel_size, color);
         }
     }
 }
 
+/// Renders the display with the configured colors, scaling every pixel to a square of `scale` pixels
+fn screenshot_image(display: &Display, config: &EmulatorConfig, scale: u32) -> ::image::RgbaImage {
+    let colors = config.display_colors();
+    let (width, height) = display.resolution();
+    ::image::RgbaImage::from_fn(width as u32 * scale, height as u32 * scale, |x, y| {
+        let color_index = display.pixel_color((x / scale) as usize, (y / scale) as usize);
+        let [r, g, b] = colors[color_index as usize];
+        ::image::Rgba([r, g, b, 255])
+    })
+}
+
+fn save_screenshot(display: &Display, config: &EmulatorConfig, path: &str) -> Result<()> {
+    screenshot_image(display, config, SCREENSHOT_SCALE)
+        .save(path)
+        .with_context(|| format!("Failed saving screenshot '{}'", path))
+}
+
+/// Uses the window size of the config, errors are reported once `main` loads the config again
 fn window_conf() -> Conf {
+    let config = load_startup_config()
+        .map(|(_, _, config)| config)
+        .unwrap_or_default();
+
     Conf {
         window_title: "Chip8 Emulator".to_owned(),
-        window_height: 1080,
-        window_width: 1920,
+        window_height: config.window_height,
+        window_width: config.window_width,
+        fullscreen: config.fullscreen,
         ..Default::default()
     }
 }
@@ -738,29 +1898,39 @@ fn window_conf() -> Conf {
 fn draw_break_point_list(ui: &mut egui::Ui, state: &mut UiState) {
     ui.heading("Break Points");
     ui.horizontal(|ui| {
-        let text_color = if state.breakpoint_input.len() != 4
-            || state
-                .breakpoint_input
-                .chars()
-                .any(|c| !c.is_ascii_hexdigit())
-        {
-            Some(egui::Color32::RED)
-        } else {
-            None
-        };
+        ui.label("New breakpoint:");
+        egui::ComboBox::from_id_source("breakpoint_kind")
+            .selected_text(format!("{:?}", state.breakpoint_kind))
+            .width(80.0)
+            .show_ui(ui, |ui| {
+                for kind in [BreakpointKind::Address, BreakpointKind::Register] {
+                    ui.selectable_value(&mut state.breakpoint_kind, kind, format!("{:?}", kind));
+                }
+            });
+
+        if state.breakpoint_kind == BreakpointKind::Register {
+            egui::ComboBox::from_id_source("breakpoint_register")
+                .selected_text(format!("V{:X}", *state.breakpoint_register))
+                .width(50.0)
+                .show_ui(ui, |ui| {
+                    for register in (0..16).map(U4::new) {
+                        let label = format!("V{:X}", *register);
+                        ui.selectable_value(&mut state.breakpoint_register, register, label);
+                    }
+                });
+        }
 
+        let breakpoint = state.parse_breakpoint_input();
+        let text_color = breakpoint.is_none().then_some(egui::Color32::RED);
         let text_edit = egui::TextEdit::singleline(&mut state.breakpoint_input)
-            .desired_width(120.0)
+            .desired_width(60.0)
             .text_color_opt(text_color);
-
-        ui.label("New breakpoint:");
         ui.add(text_edit);
-        ui.add_enabled_ui(text_color == None, |ui| {
+
+        ui.add_enabled_ui(breakpoint.is_some(), |ui| {
             if ui.button("Add").clicked() {
-                let address = u16::from_str_radix(&state.breakpoint_input, 16).unwrap();
-                if !state.breakpoint_addresses.contains(&address) {
-                    state.breakpoint_addresses.push(address);
-                    state.breakpoint_addresses.sort();
+                if let Some(breakpoint) = breakpoint {
+                    state.add_breakpoint(breakpoint);
                 }
                 state.breakpoint_input = "".to_string();
             }
@@ -769,119 +1939,766 @@ fn draw_break_point_list(ui: &mut egui::Ui, state: &mut UiState) {
 
     ui.separator();
 
-    let mut label_idx_to_remove = Option::None;
-    for address in state.breakpoint_addresses.iter() {
+    let mut idx_to_remove = None;
+    for (idx, breakpoint) in state.breakpoints.iter().enumerate() {
         ui.horizontal(|ui| {
-            ui.monospace(format!("0x{:0>4X}", address));
+            ui.monospace(breakpoint.to_string());
             if ui.button("X").clicked() {
-                label_idx_to_remove = state
-                    .breakpoint_addresses
-                    .iter()
-                    .enumerate()
-                    .find(|(_, a)| *a == address)
-                    .map(|(idx, _)| idx)
+                idx_to_remove = Some(idx);
             }
         });
     }
 
-    if let Some(idx) = label_idx_to_remove {
-        state.breakpoint_addresses.remove(idx);
+    if let Some(idx) = idx_to_remove {
+        state.breakpoints.remove(idx);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
+
+    /// File in the temp directory, removed again when the test ends
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(name))
+        }
+
+        fn path(&self) -> String {
+            self.0.to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
 
     #[test]
     fn byte_search_expanded_correctly() {
-        assert_eq!(vec![vec![6]], handle_byte_search_conversion("6").unwrap());
+        let pattern = |input| expand_byte_search(&handle_byte_search_conversion(input).unwrap());
+
+        assert_eq!(vec![Some(6)], pattern("6"));
+        assert_eq!(vec![Some(0x76)], pattern("76"));
+        assert_eq!(vec![Some(0x7), Some(0x68)], pattern("768"));
+        assert_eq!(vec![None], pattern("?"));
+        assert_eq!(vec![None], pattern("A?"));
+        assert_eq!(vec![Some(0x12), None, Some(0x34)], pattern("12??34"));
+    }
+
+    #[test]
+    fn parse_byte_search_handles_edge_cases() {
+        let byte = |value, mask| SearchByte { value, mask };
+        let cases: [(&str, Option<Vec<SearchByte>>); 8] = [
+            ("", Some(vec![])),
+            ("0", Some(vec![byte(0x00, 0xFF)])),
+            ("FF", Some(vec![byte(0xFF, 0xFF)])),
+            ("FFF", Some(vec![byte(0x0F, 0xFF), byte(0xFF, 0xFF)])),
+            ("0?", Some(vec![byte(0x00, 0xF0)])),
+            ("?0", Some(vec![byte(0x00, 0x0F)])),
+            ("??", Some(vec![byte(0x00, 0x00)])),
+            ("G0", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                expected,
+                handle_byte_search_conversion(input).ok(),
+                "Unexpected result for '{}'",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn compute_byte_indexes_to_highlight_correclty_finds_indexes() {
+        let mut memory = Memory::new();
+        memory
+            .write_slice(MemoryAddress::from_u16(0), &[0x65, 0x00, 0x64, 0x02])
+            .unwrap();
+        let highlighted = |filter| {
+            let search = handle_byte_search_conversion(filter).unwrap();
+            let starts = find_byte_search(&search, &memory);
+            let mut res = compute_byte_indexes_to_highlight(&starts, search.len())
+                .into_iter()
+                .map(usize::from)
+                .collect::<Vec<_>>();
+            res.sort();
+            res
+        };
+
+        assert_eq!(vec![3], highlighted("2"));
+        assert_eq!(vec![2], highlighted("64"));
+        assert_eq!(vec![2, 3], highlighted("6402"));
+        assert_eq!(vec![0, 2], highlighted("6?"));
+        assert_eq!(vec![0, 1, 2, 3], highlighted("6?0?"));
+        assert_eq!(vec![2], highlighted("?4"));
+    }
+
+    #[test]
+    fn search_occurrences_are_counted_per_sequence() {
+        let mut memory = Memory::new();
+        memory
+            .write_slice(
+                MemoryAddress::from_u16(0),
+                &[0x65, 0x00, 0x64, 0x02, 0x64, 0x02],
+            )
+            .unwrap();
+        let count = |filter| {
+            let search = handle_byte_search_conversion(filter).unwrap();
+            find_byte_search(&search, &memory).len()
+        };
+
+        assert_eq!(2, count("6402"));
+        assert_eq!(3, count("6?"));
+        assert_eq!(0, count("FF"));
+        assert_eq!(3, count("6?0?"));
+    }
+
+    #[test]
+    fn apply_register_edit_sets_value_and_marks_register() {
+        let mut state = UiState::default();
+
+        state
+            .apply_register_edit(EditableRegister::Variable(3), "a5")
+            .unwrap();
+        state
+            .apply_register_edit(EditableRegister::Index, "0300")
+            .unwrap();
+
+        assert_eq!(0xA5, state.cpu.get_register(U4::new(3)));
+        assert_eq!(0x300, state.cpu.index_value());
+        assert!(state
+            .modified_registers
+            .contains(&EditableRegister::Variable(3)));
+        assert!(state.modified_registers.contains(&EditableRegister::Index));
+        assert!(!state
+            .modified_registers
+            .contains(&EditableRegister::ProgramCounter));
+    }
+
+    #[test]
+    fn apply_register_edit_aligns_program_counter() {
+        let mut state = UiState::default();
+
+        state
+            .apply_register_edit(EditableRegister::ProgramCounter, "0301")
+            .unwrap();
+        state
+            .apply_register_edit(EditableRegister::Index, "0301")
+            .unwrap();
+
+        assert_eq!(0x300, state.cpu.pc());
+        assert_eq!(0x301, state.cpu.index_value());
+    }
+
+    #[test]
+    fn apply_register_edit_rejects_invalid_values() {
+        let mut state = UiState::default();
+
+        assert!(state
+            .apply_register_edit(EditableRegister::Variable(0), "1FF")
+            .is_err());
+        assert!(state
+            .apply_register_edit(EditableRegister::ProgramCounter, "zz")
+            .is_err());
+        assert!(state
+            .apply_register_edit(EditableRegister::ProgramCounter, "1000")
+            .is_err());
+        assert!(state.modified_registers.is_empty());
+    }
+
+    #[test]
+    fn step_back_restores_program_counter() {
+        let instructions = vec![0x6001, 0x6102, 0x1200];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            execution: CpuExecution::Paused,
+            ..Default::default()
+        };
+
+        state.handle_tick();
+        state.handle_tick();
+        assert_eq!(0x204, state.cpu.pc());
+
+        state.step_back();
+        assert_eq!(0x202, state.cpu.pc());
+        assert_eq!(0, state.cpu.get_register(U4::new(1)));
+
+        state.step_back();
+        assert_eq!(0x200, state.cpu.pc());
+        assert!(state.snapshots.is_empty());
+    }
+
+    #[test]
+    fn step_back_removes_the_last_trace_entry() {
+        let instructions = vec![0x6001, 0x6102, 0x1200];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            execution: CpuExecution::Paused,
+            ..Default::default()
+        };
+
+        state.handle_tick();
+        state.handle_tick();
+        state.step_back();
+
+        assert_eq!(1, state.trace.len());
+        assert_eq!(0x200, *state.trace[0].address);
+    }
+
+    #[test]
+    fn failed_step_back_keeps_the_trace() {
+        let instructions = vec![0x6001, 0x6102, 0x1200];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            execution: CpuExecution::Paused,
+            ..Default::default()
+        };
+        state.handle_tick();
+        state.handle_tick();
+        let other_cpu = Cpu::<MacroquadKeypad>::from_rom(Rom::from_raw_instructions(&[0x1200]));
+        *state.snapshots.back_mut().unwrap() = other_cpu.unwrap().save_state();
+
+        state.step_back();
+
+        assert!(state.execution == CpuExecution::Crashed);
+        assert_eq!(2, state.trace.len());
+    }
+
+    #[test]
+    fn snapshots_are_limited() {
+        let instructions = vec![0x1200];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            execution: CpuExecution::Paused,
+            ..Default::default()
+        };
+
+        for _ in 0..(MAX_SNAPSHOTS + 10) {
+            state.handle_tick();
+        }
+
+        assert_eq!(MAX_SNAPSHOTS, state.snapshots.len());
+    }
+
+    #[test]
+    fn default_key_mapping_matches_default_keypad() {
+        let default_keypad = MacroquadKeypad::default();
+        let keypad = MacroquadKeypad::from_mapping(config::DEFAULT_KEY_MAPPING).unwrap();
+
+        for value in 0..16 {
+            assert_eq!(
+                default_keypad.convert_value(value),
+                keypad.convert_value(value)
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_key_mappings_are_rejected() {
+        assert!(MacroquadKeypad::from_mapping("X123").is_err());
+        assert!(MacroquadKeypad::from_mapping("X123QWEASDZC4RF!").is_err());
+        assert!(MacroquadKeypad::from_mapping("X123QWEASDZC4RFX").is_err());
+    }
+
+    #[test]
+    fn find_roms_lists_rom_files() {
+        let roms = find_roms(Path::new("./roms")).unwrap();
+
+        assert!(roms.iter().any(|rom| rom.ends_with("ibm-logo.ch8")));
+        assert!(roms.iter().all(|rom| rom.ends_with(".ch8")));
+    }
+
+    #[test]
+    fn format_timer_shows_remaining_ticks_while_active() {
+        let mut timer = Timer::with_cpu_rate(180);
+        assert_eq!("0000", format_timer(&timer));
+
+        timer.set(0x0A);
+        timer.tick();
+        assert_eq!("000A (decrement in 2 ticks)", format_timer(&timer));
+    }
+
+    #[test]
+    fn step_records_changes_while_paused() {
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&[0x6342])).unwrap(),
+            execution: CpuExecution::Paused,
+            ..Default::default()
+        };
+
+        state.handle_tick();
+
+        let diff = state.last_diff.as_ref().unwrap();
+        assert_eq!(1, diff.changed_registers.len());
+        assert_eq!(0x3, *diff.changed_registers[0].0);
+
+        state.step_back();
+        assert!(state.last_diff.is_none());
+    }
+
+    #[test]
+    fn trace_is_exported_as_csv() {
+        let instructions = [0x6001, 0x6142, 0x8014, 0xA300, 0x00E0];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            execution: CpuExecution::Paused,
+            ..Default::default()
+        };
+        for _ in 0..instructions.len() {
+            state.handle_tick();
+        }
+
+        let path = std::env::temp_dir().join("chip8-trace-test.csv");
+        let path = path.to_string_lossy().to_string();
+        state.export_trace(&path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(6, lines.len());
+        assert_eq!(
+            "address,raw_opcode,mnemonic,V0,V1,V2,V3,V4,V5,V6,V7,V8,V9,VA,VB,VC,VD,VE,VF",
+            lines[0]
+        );
+        assert!(lines[1].starts_with("0x0200,0x6001,\"LD V0, 01\",0x01,0x00,"));
+        assert!(lines[3].starts_with("0x0204,0x8014,\"ADD V0, V1\",0x43,0x42,"));
+        assert!(lines[5].starts_with("0x0208,0x00E0,\"CLS\","));
+        for line in &lines[1..] {
+            let (_, registers) = line.rsplit_once('"').unwrap();
+            assert_eq!(17, registers.split(',').count(), "Unexpected row {}", line);
+        }
+    }
+
+    #[test]
+    fn trace_is_gzip_compressed_for_csv_gz_paths() {
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&[0x6001])).unwrap(),
+            execution: CpuExecution::Paused,
+            ..Default::default()
+        };
+        state.handle_tick();
+
+        let path = std::env::temp_dir().join("chip8-trace-test.csv.gz");
+        let path = path.to_string_lossy().to_string();
+        state.export_trace(&path).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let mut csv = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(file), &mut csv).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(2, csv.lines().count());
+        assert!(csv.lines().nth(1).unwrap().starts_with("0x0200,0x6001,"));
+    }
+
+    #[test]
+    fn screen_layout_keeps_the_display_between_the_panels() {
+        let layout = ScreenLayout::for_window(1920., 1080.);
+        assert_eq!(403., layout.side_panel_width);
+        assert_eq!(400., layout.memory_panel_height);
+        assert_eq!(17., layout.pixel_size);
+
+        for (width, height) in [(1920., 1080.), (1366., 768.), (1280., 768.), (640., 480.)] {
+            let layout = ScreenLayout::for_window(width, height);
+            assert!(layout.pixel_size >= 1.);
+            assert!(layout.display_x >= layout.side_panel_width);
+            assert!(layout.display_x + 64. * layout.pixel_size <= width - layout.side_panel_width);
+            assert!(layout.display_y >= 0.);
+            assert!(
+                layout.display_y + 32. * layout.pixel_size <= height - layout.memory_panel_height
+            );
+        }
+    }
+
+    fn address(value: u16) -> MemoryAddress {
+        MemoryAddress::from_u16(value)
+    }
+
+    #[test]
+    fn memory_byte_color_prioritizes_program_counter() {
         assert_eq!(
-            vec![vec![0x76]],
-            handle_byte_search_conversion("76").unwrap()
+            PC_AND_MATCH_COLOR,
+            memory_byte_color(address(0x200), address(0x200), address(0x300), true, false)
         );
         assert_eq!(
-            vec![vec![0x7, 0x68]],
-            handle_byte_search_conversion("768").unwrap()
+            egui::Color32::LIGHT_BLUE,
+            memory_byte_color(address(0x201), address(0x200), address(0x201), false, false)
         );
         assert_eq!(
-            vec![
-                vec![0x0],
-                vec![0x1],
-                vec![0x2],
-                vec![0x3],
-                vec![0x4],
-                vec![0x5],
-                vec![0x6],
-                vec![0x7],
-                vec![0x8],
-                vec![0x9],
-                vec![0xA],
-                vec![0xB],
-                vec![0xC],
-                vec![0xD],
-                vec![0xE],
-                vec![0xF],
-            ],
-            handle_byte_search_conversion("?").unwrap()
+            egui::Color32::YELLOW,
+            memory_byte_color(address(0x300), address(0x200), address(0x300), true, false)
         );
         assert_eq!(
-            vec![
-                vec![0xA0],
-                vec![0xA1],
-                vec![0xA2],
-                vec![0xA3],
-                vec![0xA4],
-                vec![0xA5],
-                vec![0xA6],
-                vec![0xA7],
-                vec![0xA8],
-                vec![0xA9],
-                vec![0xAA],
-                vec![0xAB],
-                vec![0xAC],
-                vec![0xAD],
-                vec![0xAE],
-                vec![0xAF],
-            ],
-            handle_byte_search_conversion("A?").unwrap()
+            egui::Color32::LIGHT_GREEN,
+            memory_byte_color(address(0x300), address(0x200), address(0x300), false, false)
+        );
+        assert_eq!(
+            CHANGED_MEMORY_COLOR,
+            memory_byte_color(address(0x300), address(0x200), address(0x300), false, true)
+        );
+        assert_eq!(
+            egui::Color32::TRANSPARENT,
+            memory_byte_color(address(0x202), address(0x200), address(0x300), false, false)
         );
     }
 
     #[test]
-    fn compute_byte_indexes_to_highlight_correclty_finds_indexes() {
-        let instructions = vec![0x6500, 0x6402];
-
-        let cpu =
-            Cpu::<MacroquadKeypad>::from_rom(Rom::from_raw_instructions(&instructions)).unwrap();
-        let bytes = cpu.memory.read_slice(MEMORY_START, 10).unwrap();
-
-        let filter = "2";
-        let search = handle_byte_search_conversion(filter).unwrap();
-        let res = Vec::from_iter(compute_byte_indexes_to_highlight(&search, bytes));
-        assert_eq!(vec![3], res);
-
-        let filter = "64";
-        let search = handle_byte_search_conversion(filter).unwrap();
-        let res = Vec::from_iter(compute_byte_indexes_to_highlight(&search, bytes));
-        assert_eq!(vec![2], res);
-
-        let filter = "6402";
-        let search = handle_byte_search_conversion(filter).unwrap();
-        let mut res = Vec::from_iter(compute_byte_indexes_to_highlight(&search, bytes));
-        res.sort();
-        assert_eq!(vec![2, 3], res);
-
-        let filter = "6?";
-        let search = handle_byte_search_conversion(filter).unwrap();
-        let mut res = Vec::from_iter(compute_byte_indexes_to_highlight(&search, bytes));
-        res.sort();
-        assert_eq!(vec![0, 2], res);
-
-        let filter = "6?0?";
-        let search = handle_byte_search_conversion(filter).unwrap();
-        let mut res = Vec::from_iter(compute_byte_indexes_to_highlight(&search, bytes));
-        res.sort();
-        assert_eq!(vec![0, 1, 2, 3], res);
+    fn output_keeps_newest_lines() {
+        let mut state = UiState {
+            max_output_lines: 3,
+            ..Default::default()
+        };
+
+        for line in 0..5 {
+            state.push_output(line.to_string());
+        }
+
+        assert_eq!(vec!["2", "3", "4"], state.output);
+        assert_eq!(2, state.hidden_output_lines);
+
+        state.clear_output();
+        assert!(state.output.is_empty());
+        assert_eq!(0, state.hidden_output_lines);
+    }
+
+    #[test]
+    fn zero_ticks_per_frame_does_not_advance_cpu() {
+        let instructions = vec![0x6001, 0x6102, 0x1200];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            execution: CpuExecution::Running,
+            ticks_per_frame: 0,
+            ..Default::default()
+        };
+
+        state.run_frame();
+        assert_eq!(0x200, state.cpu.pc());
+
+        state.ticks_per_frame = 2;
+        state.run_frame();
+        assert_eq!(0x204, state.cpu.pc());
+    }
+
+    #[test]
+    fn toggle_execution_only_switches_between_paused_and_running() {
+        let mut state = UiState {
+            execution: CpuExecution::Paused,
+            ..Default::default()
+        };
+
+        state.toggle_execution();
+        assert!(state.is_running());
+        state.toggle_execution();
+        assert!(state.is_paused());
+
+        for execution in [
+            CpuExecution::Unloaded,
+            CpuExecution::Crashed,
+            CpuExecution::Exited,
+        ] {
+            state.execution = execution;
+            state.toggle_execution();
+            assert!(!state.is_running());
+            assert!(!state.is_paused());
+        }
+    }
+
+    #[test]
+    fn exit_instruction_stops_execution_without_crashing() {
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&[0x6001, 0x00FD, 0x6002])).unwrap(),
+            execution: CpuExecution::Running,
+            ..Default::default()
+        };
+
+        state.run_frame();
+
+        assert!(state.execution == CpuExecution::Exited);
+        assert_eq!(1, state.cpu.get_register(U4::new(0)));
+        assert_eq!(vec!["ROM exited cleanly".to_string()], state.output);
+    }
+
+    #[test]
+    fn execution_stats_measure_the_rate_per_sample_interval() {
+        let start = Instant::now();
+        let mut stats = ExecutionStats::new(start);
+        for _ in 0..300 {
+            stats.record_tick();
+        }
+
+        stats.update(start + Duration::from_millis(500));
+        assert_eq!(0., stats.ticks_per_second);
+
+        stats.update(start + Duration::from_secs(2));
+        assert_eq!(150., stats.ticks_per_second);
+
+        for _ in 0..540 {
+            stats.record_tick();
+        }
+        stats.update(start + Duration::from_secs(3));
+        assert_eq!(540., stats.ticks_per_second);
+
+        assert_eq!(
+            "Instructions: 840 | Speed: 540 Hz | Session: 01:02:05",
+            stats.summary(start + Duration::from_secs(3725))
+        );
+    }
+
+    #[test]
+    fn ticks_are_counted_in_the_stats() {
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&[0x6001, 0x1200])).unwrap(),
+            execution: CpuExecution::Running,
+            ticks_per_frame: 10,
+            ..Default::default()
+        };
+
+        state.run_frame();
+
+        assert_eq!(10, state.stats.total_ticks);
+    }
+
+    #[test]
+    fn failing_tick_crashes_until_restart() {
+        let config = TempFile::new("chip8-crash-test.toml");
+        let mut state = UiState {
+            config_path: config.path(),
+            ..Default::default()
+        };
+        state.load_rom("./roms/ibm-logo.ch8");
+        state.cpu.set_pc(0xE00);
+        state.execution = CpuExecution::Running;
+
+        state.run_frame();
+        assert!(state.execution == CpuExecution::Crashed);
+        assert!(!state.is_running());
+        assert!(!state.is_paused());
+
+        state.restart();
+        assert!(state.is_paused());
+    }
+
+    #[test]
+    fn register_breakpoint_pauses_after_value_is_set() {
+        let instructions = vec![0x6001, 0x6542, 0x6003, 0x1200];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            execution: CpuExecution::Running,
+            ..Default::default()
+        };
+        state.add_breakpoint(Breakpoint::WhenRegister {
+            register: U4::new(5),
+            value: 0x42,
+        });
+
+        state.run_frame();
+
+        assert!(state.is_paused());
+        assert_eq!(0x204, state.cpu.pc());
+    }
+
+    #[test]
+    fn continue_runs_past_register_breakpoint_that_keeps_its_value() {
+        let instructions = vec![0x6542, 0x6001, 0x6002, 0x6003, 0x6542, 0x1200];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            execution: CpuExecution::Running,
+            ..Default::default()
+        };
+        state.add_breakpoint(Breakpoint::WhenRegister {
+            register: U4::new(5),
+            value: 0x42,
+        });
+
+        state.run_frame();
+        assert!(state.is_paused());
+        assert_eq!(0x202, state.cpu.pc());
+
+        state.toggle_execution();
+        state.run_frame();
+
+        assert!(state.is_running());
+        assert_eq!(0x42, state.cpu.get_register(U4::new(5)));
+    }
+
+    #[test]
+    fn breakpoint_input_is_parsed_by_kind() {
+        let mut state = UiState {
+            breakpoint_input: "020A".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            state.parse_breakpoint_input(),
+            Some(Breakpoint::AtAddress(address)) if *address == 0x20A
+        ));
+
+        state.breakpoint_kind = BreakpointKind::Register;
+        assert!(state.parse_breakpoint_input().is_none());
+
+        state.breakpoint_input = "4F".to_string();
+        state.breakpoint_register = U4::new(0xC);
+        assert!(
+            state.parse_breakpoint_input()
+                == Some(Breakpoint::WhenRegister {
+                    register: U4::new(0xC),
+                    value: 0x4F
+                })
+        );
+    }
+
+    #[test]
+    fn memory_snapshot_is_refreshed_after_memory_writes() {
+        let instructions = vec![0x60FE, 0xA300, 0xF033, 0x1206];
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&instructions)).unwrap(),
+            memory_filter: "FE".to_string(),
+            ..Default::default()
+        };
+
+        state.refresh_memory_snapshot();
+        state.handle_tick();
+        state.handle_tick();
+        assert!(state.memory_snapshot.is_some());
+
+        state.handle_tick();
+        assert!(state.memory_snapshot.is_none());
+
+        state.refresh_memory_snapshot();
+        let snapshot = state.memory_snapshot.as_ref().unwrap();
+        assert_eq!(
+            [2, 5, 4],
+            snapshot
+                .read_slice(MemoryAddress::from_u16(0x300), 3)
+                .unwrap()
+        );
+        let (filter, matches) = state.memory_matches.as_ref().unwrap();
+        assert_eq!("FE", filter);
+        assert!(matches.contains(&MemoryAddress::from_u16(0x201)));
+    }
+
+    #[test]
+    fn memory_search_uses_the_shown_snapshot() {
+        let mut state = UiState {
+            cpu: Cpu::from_rom(Rom::from_raw_instructions(&[0x1200])).unwrap(),
+            ..Default::default()
+        };
+        state.refresh_memory_snapshot();
+        state
+            .cpu
+            .memory_mut()
+            .write_slice(MemoryAddress::from_u16(0x300), &[0xBE, 0xEF])
+            .unwrap();
+
+        state.memory_filter = "BEEF".to_string();
+        state.refresh_memory_snapshot();
+
+        assert_eq!(0, state.memory_match_count);
+        let (_, matches) = state.memory_matches.as_ref().unwrap();
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn goto_address_sets_scroll_target() {
+        let mut state = UiState {
+            goto_address: "030A".to_string(),
+            ..Default::default()
+        };
+
+        state.apply_goto_address().unwrap();
+        assert_eq!(Some(0x30A), state.scroll_target);
+
+        state.scroll_target = None;
+        for input in ["zz", "1000", ""] {
+            state.goto_address = input.to_string();
+            assert!(state.apply_goto_address().is_err());
+        }
+        assert_eq!(None, state.scroll_target);
+    }
+
+    #[test]
+    fn loading_a_rom_twice_records_one_recent_rom() {
+        let config = TempFile::new("chip8-recent-roms-test.toml");
+        let mut state = UiState {
+            config_path: config.path(),
+            ..Default::default()
+        };
+
+        state.load_rom("./roms/ibm-logo.ch8");
+        state.load_rom("./roms/ibm-logo.ch8");
+
+        assert_eq!(
+            vec!["./roms/ibm-logo.ch8"],
+            Vec::from(state.config.recent_roms.clone())
+        );
+        let saved = EmulatorConfig::load(&state.config_path).unwrap();
+        assert_eq!(state.config.recent_roms, saved.recent_roms);
+    }
+
+    #[test]
+    fn loading_a_font_refreshes_memory_views() {
+        let font_file = TempFile::new("chip8-font-test.bin");
+        std::fs::write(&font_file.0, [0xAB; 80]).unwrap();
+        let mut state = UiState {
+            font_path: font_file.path(),
+            disassembly_dirty: false,
+            ..Default::default()
+        };
+        state.refresh_memory_snapshot();
+
+        state.load_font().unwrap();
+
+        assert!(state.disassembly_dirty);
+        assert!(state.memory_snapshot.is_none());
+        assert_eq!(0xAB, state.cpu.memory()[MemoryAddress::from_u16(0)]);
+    }
+
+    #[test]
+    fn restart_does_not_save_the_config() {
+        let config = TempFile::new("chip8-restart-test.toml");
+        let mut state = UiState {
+            config_path: config.path(),
+            ..Default::default()
+        };
+        state.load_rom("./roms/ibm-logo.ch8");
+        std::fs::remove_file(&config.0).unwrap();
+
+        state.restart();
+
+        assert!(state.is_paused());
+        assert!(!config.0.exists());
+    }
+
+    #[test]
+    fn screenshot_is_scaled_and_colored() {
+        let path = std::env::temp_dir().join("chip8-screenshot-test.png");
+        let path = path.to_string_lossy().to_string();
+        let mut display = Display::default();
+        display.draw(0, 0, &[0b1000_0000]);
+        let config = EmulatorConfig::default();
+
+        save_screenshot(&display, &config, &path).unwrap();
+
+        let image = ::image::open(&path).unwrap().to_rgba8();
+        assert_eq!((256, 128), image.dimensions());
+        assert_eq!(::image::Rgba([255, 255, 255, 255]), *image.get_pixel(3, 3));
+        assert_eq!(::image::Rgba([0, 0, 0, 255]), *image.get_pixel(4, 0));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn relative_path_strips_working_directory() {
+        let current_dir = std::env::current_dir().unwrap();
+        let absolute = current_dir.join("roms").join("ibm-logo.ch8");
+
+        assert_eq!(
+            Path::new("roms").join("ibm-logo.ch8").to_string_lossy(),
+            relative_path(&absolute.to_string_lossy())
+        );
+        assert_eq!("./roms/ibm-logo.ch8", relative_path("./roms/ibm-logo.ch8"));
     }
 }
@@ -1,9 +1,12 @@
 use anyhow::{anyhow, Result};
 use std::ops::{Deref, Index, IndexMut};
 
-use crate::{bits::U4, rom::Rom};
+use crate::{
+    bits::{join_bytes, split_u16, U4},
+    rom::Rom,
+};
 
-pub const MEMORY_START: MemoryAddress = MemoryAddress(0x200);
+pub const MEMORY_START: MemoryAddress = MemoryAddress::from_u16(0x200);
 pub const MEMORY_SIZE: usize = 4096;
 
 const SINGLE_FONT_BYTE_COUNT: u16 = 5;
@@ -27,28 +30,51 @@ const FONT_DATA: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-#[derive(Clone, Copy, Debug)]
+const LARGE_FONT_START: u16 = 0x50;
+const SINGLE_LARGE_FONT_BYTE_COUNT: u16 = 10;
+
+/// SUPER-CHIP 8x10 fonts, only available for the decimal digits
+const LARGE_FONT_DATA: [u8; 100] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct MemoryAddress(u16);
 
 impl MemoryAddress {
-    pub fn from_u16(value: u16) -> Self {
+    pub const fn from_u16(value: u16) -> Self {
         MemoryAddress(value)
     }
 
-    pub fn decrement(&mut self) {
+    pub(crate) fn decrement(&mut self) {
         self.0 -= 2;
     }
 
-    pub fn increment(&mut self) {
+    pub(crate) fn increment(&mut self) {
         self.0 += 2;
     }
 
-    pub fn set(&mut self, value: u16) {
+    pub(crate) fn set(&mut self, value: u16) {
         self.0 = value;
     }
 
+    /// Adds the value, wrapping around at the end of the 16 bit address space
     pub fn add(&self, value: u16) -> MemoryAddress {
-        MemoryAddress(self.0 + value)
+        MemoryAddress(self.0.wrapping_add(value))
+    }
+
+    /// Rounds the address down to the start of a two byte instruction
+    pub fn align_to_instruction(&self) -> MemoryAddress {
+        MemoryAddress(self.0 & !1)
     }
 }
 
@@ -66,6 +92,7 @@ impl Deref for MemoryAddress {
     }
 }
 
+#[derive(Clone)]
 pub struct Memory {
     data: [u8; MEMORY_SIZE],
 }
@@ -77,6 +104,10 @@ impl Memory {
         }
     }
 
+    pub fn is_address_valid(address: MemoryAddress) -> bool {
+        (address.0 as usize) < MEMORY_SIZE
+    }
+
     pub fn from_rom(rom: Rom) -> Result<Self> {
         let rom_start = MEMORY_START.0 as usize;
         if rom.data.len() > MEMORY_SIZE - rom_start {
@@ -93,6 +124,10 @@ impl Memory {
             data[index] = *value;
         }
 
+        for (index, value) in LARGE_FONT_DATA.iter().enumerate() {
+            data[LARGE_FONT_START as usize + index] = *value;
+        }
+
         for (index, rom_value) in rom.data.into_iter().enumerate() {
             data[rom_start + index] = rom_value;
         }
@@ -100,12 +135,41 @@ impl Memory {
         Ok(Memory { data })
     }
 
+    /// Replaces the small font at the start of the memory
+    pub fn load_custom_font(&mut self, data: &[u8; 80]) -> Result<()> {
+        self.write_slice(MemoryAddress(0), data)
+    }
+
     pub fn get_address_for_font(&self, value: U4) -> MemoryAddress {
         // only consider last nible
         let raw_address = *value as u16 * SINGLE_FONT_BYTE_COUNT;
         MemoryAddress(raw_address)
     }
 
+    /// Bytes of the small font glyph for the digit, including a loaded custom font
+    pub fn get_font_slice(&self, value: U4) -> &[u8] {
+        let start = self.get_address_for_font(value).0 as usize;
+        &self.data[start..start + SINGLE_FONT_BYTE_COUNT as usize]
+    }
+
+    pub fn get_address_for_large_font(&self, value: U4) -> Result<MemoryAddress> {
+        if *value > 9 {
+            return Err(anyhow!(
+                "Large fonts only exist for decimal digits, got 0x{:X}",
+                *value
+            ));
+        }
+
+        let raw_address = LARGE_FONT_START + *value as u16 * SINGLE_LARGE_FONT_BYTE_COUNT;
+        Ok(MemoryAddress(raw_address))
+    }
+
+    /// Like `read_instruction`, but fails if the instruction does not fit into the memory
+    pub fn read_instruction_checked(&self, address: MemoryAddress) -> Result<u16> {
+        let bytes = self.read_slice(address, 2)?;
+        Ok(join_bytes(bytes[0], bytes[1]))
+    }
+
     pub fn read_instruction(&self, address: MemoryAddress) -> u16 {
         let upper = self.data[address.0 as usize] as u16;
         let lower = self.data[(address.0 + 1) as usize] as u16;
@@ -113,6 +177,38 @@ impl Memory {
         return (upper << 8) + lower;
     }
 
+    /// Iterates over the bytes and their addresses from the start address up to the end of the memory
+    pub fn iter_data(
+        &self,
+        start: MemoryAddress,
+    ) -> impl Iterator<Item = (MemoryAddress, u8)> + '_ {
+        (start.0 as usize..MEMORY_SIZE)
+            .map(move |address| (MemoryAddress(address as u16), self.data[address]))
+    }
+
+    /// Iterates over the instruction words from the start address up to the end of the memory
+    pub fn iter_instructions(
+        &self,
+        start: MemoryAddress,
+    ) -> impl Iterator<Item = (MemoryAddress, u16)> + '_ {
+        (start.0 as usize..MEMORY_SIZE - 1)
+            .step_by(2)
+            .map(move |address| {
+                let address = MemoryAddress(address as u16);
+                (address, self.read_instruction(address))
+            })
+    }
+
+    /// Writes an instruction word in big-endian order
+    pub fn write_instruction(
+        &mut self,
+        address: MemoryAddress,
+        raw_instruction: u16,
+    ) -> Result<()> {
+        let (upper, lower) = split_u16(raw_instruction);
+        self.write_slice(address, &[upper, lower])
+    }
+
     pub fn write_slice(&mut self, start: MemoryAddress, bytes: &[u8]) -> Result<()> {
         let start = usize::from(start);
         if start + bytes.len() > MEMORY_SIZE {
@@ -130,6 +226,19 @@ impl Memory {
         Ok(())
     }
 
+    /// Like `write_slice`, but rejects writes into the font and interpreter area below `MEMORY_START`
+    pub fn write_slice_checked(&mut self, start: MemoryAddress, bytes: &[u8]) -> Result<()> {
+        if start.0 < MEMORY_START.0 {
+            return Err(anyhow!(
+                "Trying to write {} bytes at address {:0>4X} which is reserved for the interpreter",
+                bytes.len(),
+                start.0
+            ));
+        }
+
+        self.write_slice(start, bytes)
+    }
+
     pub fn read_slice(&self, start: MemoryAddress, length: usize) -> Result<&[u8]> {
         let start = start.0 as usize;
         if start + length > MEMORY_SIZE {
@@ -142,6 +251,68 @@ impl Memory {
 
         Ok(&self.data[start..start + length])
     }
+
+    /// Formats memory as hex and ASCII with 16 bytes per line. The dump stops at the end of the memory.
+    pub fn hexdump(&self, start: MemoryAddress, length: usize) -> String {
+        let length = length.min(MEMORY_SIZE.saturating_sub(start.0 as usize));
+        let bytes = self.read_slice(start, length).unwrap_or(&[]);
+
+        bytes
+            .chunks(16)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let address = start.0 as usize + row * 16;
+                let hex = chunk
+                    .iter()
+                    .map(|byte| format!("{:0>2X}", byte))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let ascii = chunk
+                    .iter()
+                    .map(|&byte| {
+                        if byte.is_ascii_graphic() || byte == b' ' {
+                            byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>();
+
+                format!("{:0>4X}  {:<47}  |{}|", address, hex, ascii)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Lists every differing byte with its value in this and in the other memory
+    pub fn compare(&self, other: &Memory) -> Vec<(MemoryAddress, u8, u8)> {
+        self.data
+            .iter()
+            .zip(other.data.iter())
+            .enumerate()
+            .filter(|(_, (byte, other_byte))| byte != other_byte)
+            .map(|(idx, (&byte, &other_byte))| (MemoryAddress(idx as u16), byte, other_byte))
+            .collect()
+    }
+
+    /// Finds all addresses where the pattern starts. `None` matches any byte.
+    pub fn find_pattern(&self, pattern: &[Option<u8>]) -> Vec<MemoryAddress> {
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+
+        self.data
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| {
+                window
+                    .iter()
+                    .zip(pattern)
+                    .all(|(byte, expected)| expected.is_none() || *expected == Some(*byte))
+            })
+            .map(|(idx, _)| MemoryAddress(idx as u16))
+            .collect()
+    }
 }
 
 impl Index<MemoryAddress> for Memory {
@@ -157,3 +328,317 @@ impl IndexMut<MemoryAddress> for Memory {
         &mut self.data[usize::from(index)]
     }
 }
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESERVED_END: MemoryAddress = MemoryAddress::from_u16(0x1FF);
+
+    #[test]
+    fn font_slices_contain_the_glyph_of_each_digit() {
+        let memory = Memory::from_rom(Rom::from_raw_instructions(&[0x00E0])).unwrap();
+
+        assert_eq!(
+            &[0xF0, 0x90, 0x90, 0x90, 0xF0],
+            memory.get_font_slice(U4::new(0))
+        );
+        for (digit, glyph) in FONT_DATA.chunks(5).enumerate() {
+            assert_eq!(glyph, memory.get_font_slice(U4::new(digit as u8)));
+        }
+    }
+
+    #[test]
+    fn font_slices_reflect_a_custom_font() {
+        let mut memory = Memory::new();
+        let font: [u8; 80] = std::array::from_fn(|idx| idx as u8);
+        memory.load_custom_font(&font).unwrap();
+
+        assert_eq!(&[75, 76, 77, 78, 79], memory.get_font_slice(U4::new(0xF)));
+    }
+
+    #[test]
+    fn from_u16_is_usable_in_const_context() {
+        assert_eq!(0x1FF, *RESERVED_END);
+        assert_eq!(0x200, *MEMORY_START);
+    }
+
+    #[test]
+    fn compare_lists_differing_bytes() {
+        let memory = Memory::from_rom(Rom::from_raw_instructions(&[0x00E0, 0xA22A])).unwrap();
+        let mut other = memory.clone();
+        assert!(memory.compare(&other).is_empty());
+
+        other
+            .write_slice(MemoryAddress(0x201), &[0xE0, 0xFF])
+            .unwrap();
+        other[MemoryAddress(0xFFF)] = 0x42;
+
+        assert_eq!(
+            vec![
+                (MemoryAddress(0x202), 0xA2, 0xFF),
+                (MemoryAddress(0xFFF), 0x00, 0x42),
+            ],
+            memory.compare(&other)
+        );
+    }
+
+    #[test]
+    fn read_instruction_checked_fails_past_the_end_of_memory() {
+        let mut memory = Memory::new();
+        memory
+            .write_slice(MemoryAddress(0xFFE), &[0x12, 0x34])
+            .unwrap();
+
+        assert_eq!(
+            0x1234,
+            memory
+                .read_instruction_checked(MemoryAddress(0xFFE))
+                .unwrap()
+        );
+        assert!(memory
+            .read_instruction_checked(MemoryAddress(0xFFF))
+            .is_err());
+        assert!(memory
+            .read_instruction_checked(MemoryAddress(0x1000))
+            .is_err());
+    }
+
+    #[test]
+    fn find_pattern_matches_exact_bytes() {
+        let rom = Rom::from_raw_instructions(&[0x6A02, 0x6B03, 0x6A04]);
+        let memory = Memory::from_rom(rom).unwrap();
+
+        let addresses = memory
+            .find_pattern(&[Some(0x6A), Some(0x04)])
+            .into_iter()
+            .map(|a| *a)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![0x204], addresses);
+    }
+
+    #[test]
+    fn find_pattern_treats_none_as_wildcard() {
+        let rom = Rom::from_raw_instructions(&[0x6A02, 0x6B03, 0x6A04]);
+        let memory = Memory::from_rom(rom).unwrap();
+
+        let addresses = memory
+            .find_pattern(&[Some(0x6A), None])
+            .into_iter()
+            .map(|a| *a)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![0x200, 0x204], addresses);
+    }
+
+    #[test]
+    fn find_pattern_finds_all_addresses_followed_by_zero() {
+        let rom = Rom::from_raw_instructions(&[0x6A00, 0x6B03, 0x6A04]);
+        let memory = Memory::from_rom(rom).unwrap();
+
+        let addresses = memory.find_pattern(&[None, Some(0x00)]);
+        let expected_count = memory.data.windows(2).filter(|w| w[1] == 0).count();
+
+        assert_eq!(expected_count, addresses.len());
+        assert!(addresses.iter().all(|a| memory[a.add(1)] == 0));
+        assert!(addresses.iter().any(|a| **a == 0x200));
+        assert!(!addresses.iter().any(|a| **a == 0x202));
+    }
+
+    #[test]
+    fn find_pattern_with_empty_pattern_finds_nothing() {
+        let memory = Memory::new();
+
+        assert!(memory.find_pattern(&[]).is_empty());
+    }
+
+    #[test]
+    fn hexdump_of_font_data() {
+        let memory = Memory::from_rom(Rom::from_raw_instructions(&[])).unwrap();
+
+        let dump = memory.hexdump(MemoryAddress::from_u16(0), 16);
+
+        assert_eq!(
+            "0000  F0 90 90 90 F0 20 60 20 20 70 F0 10 F0 80 F0 F0  |..... `  p......|",
+            dump
+        );
+    }
+
+    #[test]
+    fn hexdump_pads_incomplete_lines() {
+        let mut memory = Memory::new();
+        memory
+            .write_slice(MemoryAddress::from_u16(0x300), b"Hello, world!\0 CHIP-8")
+            .unwrap();
+
+        let dump = memory.hexdump(MemoryAddress::from_u16(0x300), 21);
+
+        assert_eq!(
+            "0300  48 65 6C 6C 6F 2C 20 77 6F 72 6C 64 21 00 20 43  |Hello, world!. C|\n\
+             0310  48 49 50 2D 38                                   |HIP-8|",
+            dump
+        );
+    }
+
+    #[test]
+    fn hexdump_stops_at_end_of_memory() {
+        let memory = Memory::new();
+
+        let dump = memory.hexdump(MemoryAddress::from_u16(0xFFC), 16);
+
+        assert_eq!(
+            "0FFC  00 00 00 00                                      |....|",
+            dump
+        );
+    }
+
+    #[test]
+    fn iter_instructions_yields_addresses_and_words() {
+        let memory =
+            Memory::from_rom(Rom::from_raw_instructions(&[0x00E0, 0xA22A, 0x1200])).unwrap();
+
+        let instructions = memory
+            .iter_instructions(MEMORY_START)
+            .take(4)
+            .map(|(address, word)| (*address, word))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (0x200, 0x00E0),
+                (0x202, 0xA22A),
+                (0x204, 0x1200),
+                (0x206, 0x0000)
+            ],
+            instructions
+        );
+    }
+
+    #[test]
+    fn iter_data_yields_addresses_and_bytes() {
+        let memory = Memory::from_rom(Rom::from_raw_instructions(&[0x00E0, 0xA22A])).unwrap();
+
+        let data = memory.iter_data(MEMORY_START).take(4).collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (MemoryAddress(0x200), 0x00),
+                (MemoryAddress(0x201), 0xE0),
+                (MemoryAddress(0x202), 0xA2),
+                (MemoryAddress(0x203), 0x2A),
+            ],
+            data
+        );
+        assert_eq!(MEMORY_SIZE, memory.iter_data(MemoryAddress(0)).count());
+        assert_eq!(
+            Some((MemoryAddress(0xFFF), 0)),
+            memory.iter_data(MemoryAddress(0xFFE)).last()
+        );
+    }
+
+    #[test]
+    fn iter_instructions_stops_at_end_of_memory() {
+        let memory = Memory::new();
+
+        assert_eq!(
+            MEMORY_SIZE / 2,
+            memory.iter_instructions(MemoryAddress(0)).count()
+        );
+        let (last_address, _) = memory
+            .iter_instructions(MemoryAddress(0xFFC))
+            .last()
+            .unwrap();
+        assert_eq!(0xFFE, *last_address);
+        assert_eq!(0, memory.iter_instructions(MemoryAddress(0xFFF)).count());
+    }
+
+    #[test]
+    fn written_instruction_is_read_back() {
+        let mut memory = Memory::new();
+
+        memory
+            .write_instruction(MemoryAddress::from_u16(0x2A0), 0xD01F)
+            .unwrap();
+
+        assert_eq!(
+            0xD01F,
+            memory.read_instruction(MemoryAddress::from_u16(0x2A0))
+        );
+        assert_eq!(0xD0, memory[MemoryAddress::from_u16(0x2A0)]);
+        assert_eq!(0x1F, memory[MemoryAddress::from_u16(0x2A1)]);
+    }
+
+    #[test]
+    fn addresses_are_aligned_to_instructions() {
+        assert_eq!(
+            MemoryAddress::from_u16(0x200),
+            MemoryAddress::from_u16(0x201).align_to_instruction()
+        );
+        assert_eq!(
+            MemoryAddress::from_u16(0x200),
+            MemoryAddress::from_u16(0x200).align_to_instruction()
+        );
+    }
+
+    #[test]
+    fn checked_writes_are_limited_to_the_rom_area() {
+        let mut memory = Memory::new();
+
+        assert!(memory
+            .write_slice_checked(MemoryAddress(0x100), &[1, 2])
+            .is_err());
+        assert!(memory
+            .write_slice_checked(MemoryAddress(0x1FF), &[1, 2])
+            .is_err());
+        assert_eq!([0, 0], memory.read_slice(MemoryAddress(0x1FF), 2).unwrap());
+
+        memory
+            .write_slice_checked(MemoryAddress(0x200), &[1, 2])
+            .unwrap();
+        assert_eq!([1, 2], memory.read_slice(MemoryAddress(0x200), 2).unwrap());
+        assert!(memory
+            .write_slice_checked(MemoryAddress(0xFFF), &[1, 2])
+            .is_err());
+    }
+
+    #[test]
+    fn addresses_are_valid_within_memory() {
+        assert!(Memory::is_address_valid(MemoryAddress(0x000)));
+        assert!(Memory::is_address_valid(MemoryAddress(0xFFF)));
+        assert!(!Memory::is_address_valid(MemoryAddress(0x1000)));
+    }
+
+    #[test]
+    fn write_instruction_fails_at_end_of_memory() {
+        let mut memory = Memory::new();
+
+        assert!(memory
+            .write_instruction(MemoryAddress::from_u16(0xFFF), 0x00E0)
+            .is_err());
+        assert!(memory
+            .write_instruction(MemoryAddress::from_u16(0xFFE), 0x00E0)
+            .is_ok());
+    }
+
+    #[test]
+    fn large_fonts_follow_standard_fonts() {
+        let memory = Memory::from_rom(Rom::from_raw_instructions(&[])).unwrap();
+
+        let address = memory.get_address_for_large_font(U4::new(9)).unwrap();
+
+        assert_eq!(0xAA, *address);
+        assert_eq!(
+            &LARGE_FONT_DATA[90..],
+            memory.read_slice(address, 10).unwrap()
+        );
+        assert_eq!(0xF0, memory[MemoryAddress::from_u16(0x4B)]);
+        assert!(memory.get_address_for_large_font(U4::new(0xA)).is_err());
+    }
+}
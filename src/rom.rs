@@ -1,11 +1,104 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
 use std::io::BufReader;
+use std::path::Path;
 use std::{fs::File, io::Read};
 
 use crate::bits::{join_bytes, join_to_u16, split_u16};
+use crate::cpu::CompatibilityMode;
+use crate::instruction::Instruction;
+use crate::memory::MEMORY_START;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationWarning {
+    OddLength { length: usize },
+    InvalidFirstInstruction { raw_instruction: u16 },
+    MissingReturn { call_address: u16, target: u16 },
+    RecursiveCall { address: u16 },
+}
+
+impl ValidationWarning {
+    pub fn severity(&self) -> Severity {
+        match self {
+            ValidationWarning::OddLength { .. } => Severity::Warning,
+            ValidationWarning::InvalidFirstInstruction { .. } => Severity::Error,
+            ValidationWarning::MissingReturn { .. } => Severity::Info,
+            ValidationWarning::RecursiveCall { .. } => Severity::Error,
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: ", self.severity())?;
+        match self {
+            ValidationWarning::OddLength { length } => {
+                write!(f, "Rom has an odd length of {} bytes", length)
+            }
+            ValidationWarning::InvalidFirstInstruction { raw_instruction } => write!(
+                f,
+                "First instruction 0x{:0>4X} is not a valid opcode",
+                raw_instruction
+            ),
+            ValidationWarning::MissingReturn {
+                call_address,
+                target,
+            } => write!(
+                f,
+                "Subroutine 0x{:0>4X} called at 0x{:0>4X} has no matching return",
+                target, call_address
+            ),
+            ValidationWarning::RecursiveCall { address } => write!(
+                f,
+                "Subroutine call at 0x{:0>4X} calls itself and overflows the stack",
+                address
+            ),
+        }
+    }
+}
+
+/// Optional information about a rom, read from a `<rom>.toml` file next to the rom. The sidecar uses
+/// TOML like the emulator config, the crate has no JSON parser.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RomMetadata {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    /// Overrides the globally configured compatibility mode
+    pub compatibility_mode: Option<CompatibilityMode>,
+}
+
+impl RomMetadata {
+    /// Loads the sidecar file of the rom at the given path, if there is one
+    pub fn load_sidecar(rom_path: &str) -> Result<Option<Self>> {
+        let path = format!("{}.toml", rom_path);
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+
+        let content =
+            std::fs::read_to_string(&path).with_context(|| format!("Failed reading '{}'", path))?;
+        let metadata =
+            Self::from_toml(&content).with_context(|| format!("Failed parsing '{}'", path))?;
+        Ok(Some(metadata))
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+}
 
 pub struct Rom {
     pub data: Vec<u8>,
+    pub metadata: Option<RomMetadata>,
 }
 
 impl Rom {
@@ -16,29 +109,343 @@ impl Rom {
             rom_data.push(b2);
         });
 
-        Self { data: rom_data }
+        Self {
+            data: rom_data,
+            metadata: None,
+        }
     }
 
-    pub fn from_file(file_path: &str) -> Result<Self> {
-        let mut file = File::open(file_path)?;
+    /// Parses pairs of hex digits into bytes, whitespace between the digits is ignored
+    pub fn from_hex_string(hex: &str) -> Result<Self> {
+        let digits = hex
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<Vec<_>>();
+        if let Some(invalid) = digits.iter().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(anyhow!("Invalid hex character '{}'", invalid));
+        }
+        if digits.len() % 2 != 0 {
+            return Err(anyhow!(
+                "Expected pairs of hex digits, got {} digits",
+                digits.len()
+            ));
+        }
 
+        let data = digits
+            .chunks(2)
+            .map(|pair| {
+                let byte = format!("{}{}", pair[0], pair[1]);
+                u8::from_str_radix(&byte, 16).expect("hex digits")
+            })
+            .collect();
+
+        Ok(Self {
+            data,
+            metadata: None,
+        })
+    }
+
+    /// Reads the rom data until the end of the reader, without metadata
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
         let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+        BufReader::new(reader)
+            .read_to_end(&mut data)
+            .with_context(|| "Failed reading rom data")?;
 
-        Ok(Self { data })
+        Ok(Self {
+            data,
+            metadata: None,
+        })
+    }
+
+    pub fn from_file(file_path: &str) -> Result<Self> {
+        let file = File::open(file_path)?;
+        let rom = Self::from_reader(file)?;
+
+        let metadata = RomMetadata::load_sidecar(file_path)?;
+        Ok(Self { metadata, ..rom })
+    }
+
+    /// Iterates over the byte offsets and instruction words of the rom.
+    /// A trailing byte of a rom with an odd length is ignored.
+    pub fn iter_instructions(&self) -> impl Iterator<Item = (usize, u16)> + '_ {
+        self.data
+            .chunks_exact(2)
+            .enumerate()
+            .map(|(idx, chunk)| (idx * 2, join_bytes(chunk[0], chunk[1])))
+    }
+
+    /// Checks if any instruction word of the rom calls the subroutine at the target address
+    pub fn contains_subroutine_at(&self, target: u16) -> bool {
+        self.any_instruction(|instruction| {
+            matches!(instruction, Instruction::CallSubroutine(address) if **address == target)
+        })
+    }
+
+    /// Checks if any instruction word of the rom jumps to the target address. For `JumpWithOffset`
+    /// only the base address is compared, as the offset in V0 is only known at runtime.
+    pub fn contains_jump_to(&self, target: u16) -> bool {
+        self.any_instruction(|instruction| match instruction {
+            Instruction::Jump(address) | Instruction::JumpWithOffset(address) => *address == target,
+            _ => false,
+        })
+    }
+
+    fn any_instruction(&self, predicate: impl Fn(&Instruction) -> bool) -> bool {
+        self.iter_instructions()
+            .filter_map(|(_, raw_instruction)| Instruction::try_from_u16(raw_instruction).ok())
+            .any(|instruction| predicate(&instruction))
+    }
+
+    /// CRC32 of the rom data, used to identify the rom a cpu state belongs to
+    pub fn checksum(&self) -> u32 {
+        crc32fast::hash(&self.data)
+    }
+
+    /// Statically checks the rom for obvious issues before loading it.
+    pub fn validate(&self) -> Result<Vec<ValidationWarning>> {
+        if self.data.is_empty() {
+            return Err(anyhow!("Rom does not contain any data"));
+        }
+
+        let mut warnings = Vec::new();
+        if !self.data.len().is_multiple_of(2) {
+            warnings.push(ValidationWarning::OddLength {
+                length: self.data.len(),
+            });
+        }
+
+        let instructions = self
+            .iter_instructions()
+            .map(|(offset, raw_instruction)| {
+                let address = *MEMORY_START + offset as u16;
+                (
+                    address,
+                    raw_instruction,
+                    Instruction::try_from_u16(raw_instruction),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if let Some((_, raw_instruction, Err(_))) = instructions.first() {
+            warnings.push(ValidationWarning::InvalidFirstInstruction {
+                raw_instruction: *raw_instruction,
+            });
+        }
+
+        for (call_address, _, instruction) in instructions.iter() {
+            let Ok(Instruction::CallSubroutine(target)) = instruction else {
+                continue;
+            };
+
+            if **target == *call_address {
+                warnings.push(ValidationWarning::RecursiveCall {
+                    address: *call_address,
+                });
+                continue;
+            }
+
+            let has_return = instructions
+                .iter()
+                .skip_while(|(address, _, _)| address < &**target)
+                .any(|(_, _, instruction)| matches!(instruction, Ok(Instruction::Return)));
+            if !has_return {
+                warnings.push(ValidationWarning::MissingReturn {
+                    call_address: *call_address,
+                    target: **target,
+                });
+            }
+        }
+
+        Ok(warnings)
     }
 }
 
 impl std::fmt::Debug for Rom {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut dbg = f.debug_list();
-        self.data
-            .chunks(2)
-            .map(|chunk| join_bytes(chunk[0], chunk[1]))
-            .for_each(|value| {
-                dbg.entry(&format_args!("0x{:0>4X}", value));
-            });
+        self.iter_instructions().for_each(|(_, value)| {
+            dbg.entry(&format_args!("0x{:0>4X}", value));
+        });
 
         dbg.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn from_reader_reads_all_bytes() {
+        let bytes: &[u8] = &[0x00, 0xE0, 0xA2, 0x2A, 0x12];
+
+        let rom = Rom::from_reader(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(bytes, rom.data);
+        assert!(rom.metadata.is_none());
+
+        let rom = Rom::from_reader(Cursor::new([0x00, 0xE0, 0xA2, 0x2A])).unwrap();
+        assert_eq!(Rom::from_raw_instructions(&[0x00E0, 0xA22A]).data, rom.data);
+    }
+
+    #[test]
+    fn subroutine_calls_are_found_statically() {
+        let rom = Rom::from_raw_instructions(&[0x00E0, 0x2300, 0xFFFF, 0x1202]);
+
+        assert!(rom.contains_subroutine_at(0x300));
+        assert!(!rom.contains_subroutine_at(0x202));
+        assert!(!rom.contains_subroutine_at(0x0E0));
+    }
+
+    #[test]
+    fn jumps_are_found_statically() {
+        let rom = Rom::from_raw_instructions(&[0x2300, 0x1202, 0xB400]);
+
+        assert!(rom.contains_jump_to(0x202));
+        assert!(rom.contains_jump_to(0x400));
+        assert!(!rom.contains_jump_to(0x300));
+    }
+
+    #[test]
+    fn from_hex_string_matches_raw_instructions() {
+        let rom = Rom::from_hex_string("00E0 a200\n  6000\t").unwrap();
+
+        assert_eq!(
+            Rom::from_raw_instructions(&[0x00E0, 0xA200, 0x6000]).data,
+            rom.data
+        );
+    }
+
+    #[test]
+    fn from_hex_string_rejects_invalid_input() {
+        assert!(Rom::from_hex_string("00E0 A2 0").is_err());
+        assert!(Rom::from_hex_string("00EG").is_err());
+        assert!(Rom::from_hex_string("").unwrap().data.is_empty());
+    }
+
+    #[test]
+    fn validate_accepts_well_formed_rom() {
+        let rom = Rom::from_raw_instructions(&[0x2204, 0x1202, 0x6001, 0x00EE]);
+
+        assert!(rom.validate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_empty_rom() {
+        let rom = Rom {
+            data: Vec::new(),
+            metadata: None,
+        };
+
+        assert!(rom.validate().is_err());
+    }
+
+    #[test]
+    fn validate_reports_odd_length_and_invalid_first_instruction() {
+        let rom = Rom {
+            data: vec![0xFF, 0xFF, 0x60],
+            metadata: None,
+        };
+
+        let warnings = rom.validate().unwrap();
+
+        assert_eq!(
+            vec![
+                ValidationWarning::OddLength { length: 3 },
+                ValidationWarning::InvalidFirstInstruction {
+                    raw_instruction: 0xFFFF
+                },
+            ],
+            warnings
+        );
+    }
+
+    #[test]
+    fn validate_reports_problematic_subroutine_calls() {
+        let rom = Rom::from_raw_instructions(&[0x2204, 0x2202, 0x6001]);
+
+        let warnings = rom.validate().unwrap();
+
+        assert_eq!(
+            vec![
+                ValidationWarning::MissingReturn {
+                    call_address: 0x200,
+                    target: 0x204
+                },
+                ValidationWarning::RecursiveCall { address: 0x202 },
+            ],
+            warnings
+        );
+        assert_eq!(Severity::Error, warnings[1].severity());
+    }
+
+    #[test]
+    fn iter_instructions_yields_offsets_and_opcodes() {
+        let rom = Rom::from_raw_instructions(&[0x00E0, 0xA22A, 0x1200]);
+
+        assert_eq!(
+            vec![(0, 0x00E0), (2, 0xA22A), (4, 0x1200)],
+            rom.iter_instructions().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn iter_instructions_ignores_trailing_byte() {
+        let rom = Rom {
+            data: vec![0x00, 0xE0, 0x12],
+            metadata: None,
+        };
+
+        assert_eq!(
+            vec![(0, 0x00E0)],
+            rom.iter_instructions().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn iter_instructions_of_rom_file() {
+        let rom = Rom::from_file("./roms/ibm-logo.ch8").unwrap();
+
+        let instructions = rom.iter_instructions().take(3).collect::<Vec<_>>();
+
+        assert_eq!(vec![(0, 0x00E0), (2, 0xA22A), (4, 0x600C)], instructions);
+    }
+
+    #[test]
+    fn checksum_identifies_rom_data() {
+        let rom = Rom::from_raw_instructions(&[0x00E0, 0xA22A, 0x1200]);
+        let same_rom = Rom::from_raw_instructions(&[0x00E0, 0xA22A, 0x1200]);
+        let changed_rom = Rom::from_raw_instructions(&[0x00E0, 0xA22B, 0x1200]);
+
+        assert_eq!(rom.checksum(), same_rom.checksum());
+        assert_ne!(rom.checksum(), changed_rom.checksum());
+    }
+
+    #[test]
+    fn metadata_sidecar_is_loaded_with_rom() {
+        let rom = Rom::from_file("./roms/ibm-logo.ch8").unwrap();
+
+        assert_eq!(
+            Some(RomMetadata {
+                name: Some("IBM Logo".to_string()),
+                author: Some("IBM".to_string()),
+                description: Some("Draws the IBM logo and loops forever".to_string()),
+                compatibility_mode: Some(CompatibilityMode::Chip8),
+            }),
+            rom.metadata
+        );
+    }
+
+    #[test]
+    fn metadata_fields_are_optional() {
+        let metadata = RomMetadata::from_toml("name = \"Pong\"").unwrap();
+
+        assert_eq!(Some("Pong".to_string()), metadata.name);
+        assert_eq!(None, metadata.compatibility_mode);
+        assert!(RomMetadata::load_sidecar("./roms/missing.ch8")
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,6 +1,6 @@
 const TIMER_RATE: u64 = 60;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Timer {
     value: u8,
     ticks_passed: u64,
@@ -31,6 +31,12 @@ impl Timer {
         }
     }
 
+    /// Changes the rate of a running countdown as well, already passed ticks are kept up to the new limit
+    pub fn set_cpu_rate(&mut self, cpu_tick_rate: u64) {
+        self.tick_limit = cpu_tick_rate / TIMER_RATE;
+        self.ticks_passed = self.ticks_passed.min(self.tick_limit);
+    }
+
     pub fn get(&self) -> u8 {
         self.value
     }
@@ -39,6 +45,24 @@ impl Timer {
         self.value = value;
         self.ticks_passed = 0;
     }
+
+    pub fn is_active(&self) -> bool {
+        self.value > 0
+    }
+
+    /// Returns the current value and the number of ticks until it is decremented next
+    pub fn remaining(&self) -> (u8, u64) {
+        (self.value, self.remaining_ticks())
+    }
+
+    /// Returns the number of ticks until the value is decremented next, or 0 if the timer is inactive
+    pub fn remaining_ticks(&self) -> u64 {
+        if !self.is_active() {
+            return 0;
+        }
+
+        self.tick_limit.saturating_sub(self.ticks_passed)
+    }
 }
 
 #[cfg(test)]
@@ -56,4 +80,76 @@ mod tests {
         timer.tick();
         assert_eq!(0, timer.get());
     }
+
+    #[test]
+    fn timer_becomes_inactive_when_expired() {
+        let mut timer = Timer::with_cpu_rate(TIMER_RATE * 2);
+        assert!(!timer.is_active());
+
+        timer.set(1);
+        assert!(timer.is_active());
+
+        timer.tick();
+        assert!(timer.is_active());
+
+        timer.tick();
+        assert!(!timer.is_active());
+
+        timer.tick();
+        assert!(!timer.is_active());
+    }
+
+    #[test]
+    fn timer_reports_remaining_ticks_until_decrement() {
+        let mut timer = Timer::with_cpu_rate(TIMER_RATE * 3);
+        timer.set(2);
+        assert_eq!((2, 3), timer.remaining());
+
+        timer.tick();
+        assert_eq!((2, 2), timer.remaining());
+
+        timer.tick();
+        timer.tick();
+        assert_eq!((1, 3), timer.remaining());
+    }
+
+    #[test]
+    fn lowering_the_rate_keeps_remaining_ticks_in_bounds() {
+        let mut timer = Timer::with_cpu_rate(TIMER_RATE * 5);
+        timer.set(2);
+        for _ in 0..4 {
+            timer.tick();
+        }
+        assert_eq!((2, 1), timer.remaining());
+
+        timer.set_cpu_rate(TIMER_RATE * 2);
+        assert_eq!(0, timer.remaining_ticks());
+        assert_eq!((2, 0), timer.remaining());
+
+        timer.tick();
+        assert_eq!(1, timer.get());
+        assert_eq!(2, timer.remaining_ticks());
+    }
+
+    #[test]
+    fn inactive_timer_has_no_remaining_ticks() {
+        let mut timer = Timer::with_cpu_rate(TIMER_RATE * 3);
+        assert_eq!(0, timer.remaining_ticks());
+
+        timer.set(2);
+        assert_eq!(3, timer.remaining_ticks());
+
+        timer.tick();
+        assert_eq!(2, timer.remaining_ticks());
+
+        timer.tick();
+        timer.tick();
+        assert_eq!(1, timer.get());
+        assert_eq!(3, timer.remaining_ticks());
+
+        timer.tick();
+        timer.tick();
+        timer.tick();
+        assert_eq!(0, timer.remaining_ticks());
+    }
 }
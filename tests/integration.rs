@@ -0,0 +1,160 @@
+use chip8_emulator::{
+    cpu::{CompatibilityMode, Cpu, CpuConfig, HaltReason},
+    keypad::MockKeypad,
+    rom::Rom,
+};
+
+const MAX_CYCLES: u64 = 10_000;
+
+/// Runs the rom until it loops on its result screen and returns the screen as text art
+fn run_test_rom(path: &str, mode: CompatibilityMode) -> String {
+    let rom = Rom::from_file(path).unwrap();
+    let mut cpu = Cpu::<MockKeypad>::from_rom_with_config(rom, CpuConfig::for_mode(mode)).unwrap();
+
+    let reason = cpu.run_until_halt(MAX_CYCLES);
+    assert!(
+        matches!(reason, HaltReason::InfiniteLoop { .. }),
+        "{}: {:?}",
+        path,
+        reason
+    );
+
+    cpu.display().as_text_art('#', '.')
+}
+
+#[test]
+fn bc_test_shows_the_passed_screen() {
+    // "BON" in the center is shown when all tests passed, failures show "E" and an error number
+    let expected = [
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        ".....................####.....####...#....#.....................",
+        ".....................#...#...#....#..##...#.....................",
+        ".....................#...#...#....#..#.#..#.....................",
+        ".....................####....#....#..#..#.#.....................",
+        ".....................#...#...#....#..#...##.....................",
+        ".....................#...#...#....#..#....#.....................",
+        ".....................#...#...#....#..#....#.....................",
+        ".....................####.....####...#....#.....................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "..##.............##.............#....###.........#..............",
+        "..#.#............#.#............#....#...........#..............",
+        "..#.#..#.#.......#.#...##...##..##...#.....#.....#...##.........",
+        "..##...#.#.......##...#.#..#....#....#....#.#...##..#.#...##....",
+        "..#.#..###.......#.#..##....#...#....#....#.#..#.#..##....#.....",
+        "..#.#....#.......#.#..#......#..#....#....#.#..#.#..#.....#.....",
+        "..##.....#.......##....##..##....##..###...#....##...##...#.#...",
+        ".......###......................................................",
+    ]
+    .join("\n");
+
+    assert_eq!(
+        expected,
+        run_test_rom("./roms/bc_test.ch8", CompatibilityMode::SuperChip)
+    );
+}
+
+#[test]
+fn sctest_shows_the_passed_screen() {
+    // "OK" in the top left is shown when all tests passed, failures show "ERROR" and an error number
+    let expected = [
+        "####.#..#.......................................................",
+        "#..#.#.#........................................................",
+        "#..#.##.........................................................",
+        "#..#.#.#........................................................",
+        "####.#..#.......................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+    ]
+    .join("\n");
+
+    assert_eq!(
+        expected,
+        run_test_rom("./roms/SCTEST.ch8", CompatibilityMode::SuperChip)
+    );
+}
+
+#[test]
+fn ibm_logo_is_drawn_after_20_instructions() {
+    let rom = Rom::from_file("./roms/ibm-logo.ch8").unwrap();
+    let mut cpu = Cpu::<MockKeypad>::from_rom(rom).unwrap();
+    let expected = [
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "............########.#########...#####.........#####............",
+        "................................................................",
+        "............########.###########.######.......######............",
+        "................................................................",
+        "..............####.....###...###...#####.....#####..............",
+        "................................................................",
+        "..............####.....#######.....#######.#######..............",
+        "................................................................",
+        "..............####.....#######.....###.#######.###..............",
+        "................................................................",
+        "..............####.....###...###...###..#####..###..............",
+        "................................................................",
+        "............########.###########.#####...###...#####............",
+        "................................................................",
+        "............########.#########...#####....#....#####............",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+        "................................................................",
+    ]
+    .join("\n");
+
+    for _ in 0..20 {
+        cpu.tick().unwrap();
+    }
+
+    assert_eq!(expected, cpu.display().as_text_art('#', '.'));
+}